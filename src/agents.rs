@@ -5,13 +5,13 @@ use which::which;
 
 use crate::Cli;
 use crate::config;
-use crate::config::{Agent, CommandSpec};
+use crate::config::{Agent, CommandSpec, EnvPolicy};
 use crate::error::AgentError;
 
 pub fn resolve_agent(cli: &Cli, cfg: &config::Config) -> Result<Agent> {
     // CLI overrides config; if CLI agent is set, synthesize it.
     if let Some(agent_name) = &cli.agent {
-        return synthesize_agent(agent_name, cli.model.clone());
+        return synthesize_agent(agent_name, cli.model.clone(), &cfg.env_policy);
     }
 
     // Prefer the unified agent config if present, then fall back to legacy roles.
@@ -28,7 +28,18 @@ pub fn resolve_agent(cli: &Cli, cfg: &config::Config) -> Result<Agent> {
     Err(AgentError::NotConfigured.into())
 }
 
-fn synthesize_agent(agent_name: &str, model_override: Option<String>) -> Result<Agent> {
+/// The agent that plans fixes before the solver agent applies them: `[agents.analyzer]`, as
+/// long as another agent is there to do the fixing (alone, it is the solver).
+pub fn resolve_analyzer(cli: &Cli, cfg: &config::Config) -> Option<Agent> {
+    let fixer = cli.agent.is_some() || cfg.agent.is_some() || cfg.agents.fixer.is_some();
+    cfg.agents.analyzer.clone().filter(|_| fixer)
+}
+
+fn synthesize_agent(
+    agent_name: &str,
+    model_override: Option<String>,
+    env_policy: &EnvPolicy,
+) -> Result<Agent> {
     let kind = agent_name.to_ascii_lowercase();
     let (binary, default_model) = match kind.as_str() {
         "codex" => ("codex", "gpt-5.1-codex-max"),
@@ -64,7 +75,8 @@ fn synthesize_agent(agent_name: &str, model_override: Option<String>) -> Result<
             let args = vec![
                 "--print".to_string(),
                 "--output-format".to_string(),
-                "text".to_string(),
+                "stream-json".to_string(),
+                "--verbose".to_string(),
                 "--input-format".to_string(),
                 "text".to_string(),
                 "--no-session-persistence".to_string(),
@@ -86,5 +98,11 @@ fn synthesize_agent(agent_name: &str, model_override: Option<String>) -> Result<
         },
         env: std::collections::HashMap::new(),
         timeout: Some(Duration::from_secs(300)),
+        kill_grace: None,
+        env_policy: config::agent_env_policy(true, env_policy, &[]),
+        output: config::AgentOutput::Auto,
+        max_concurrency: None,
+        rate_limit_retries: config::DEFAULT_RATE_LIMIT_RETRIES,
+        rate_limit_backoff: config::DEFAULT_RATE_LIMIT_BACKOFF,
     })
 }
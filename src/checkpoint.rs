@@ -0,0 +1,183 @@
+//! Checkpoints of the working tree taken before solvers run, so the changes of a solver that
+//! made things worse can be rolled back.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+
+use crate::history;
+
+/// The content of every file under the root that isn't ignored, as it was when captured.
+pub struct Checkpoint {
+    root: PathBuf,
+    /// Root-relative path to content; `None` for files that couldn't be read, which are
+    /// never touched on restore.
+    files: HashMap<PathBuf, Option<Vec<u8>>>,
+}
+
+impl Checkpoint {
+    pub fn capture(root: &Path) -> Self {
+        let files = walk(root)
+            .map(|(rel, path)| (rel, std::fs::read(path).ok()))
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            files,
+        }
+    }
+
+    /// Put `paths` (root-relative) back the way they were; files created since are removed.
+    pub fn restore(&self, paths: &BTreeSet<PathBuf>) -> Result<()> {
+        for rel in paths {
+            let path = self.root.join(rel);
+            match self.files.get(rel) {
+                Some(None) => {}
+                Some(Some(content)) => {
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    std::fs::write(&path, content)
+                        .with_context(|| format!("failed to restore {}", path.display()))?;
+                }
+                None if path.exists() => std::fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Content hashes of the files under a root, to tell which files changed in between.
+pub struct TreeState(HashMap<PathBuf, u64>);
+
+impl TreeState {
+    /// Best-effort: unreadable files are left out.
+    pub fn capture(root: &Path) -> Self {
+        let hashes = walk(root)
+            .filter_map(|(rel, path)| {
+                let content = std::fs::read(path).ok()?;
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                Some((rel, hasher.finish()))
+            })
+            .collect();
+        Self(hashes)
+    }
+
+    /// Root-relative paths created, modified or deleted between `self` and `later`.
+    pub fn changed(&self, later: &TreeState) -> BTreeSet<PathBuf> {
+        let modified = later
+            .0
+            .iter()
+            .filter(|(path, hash)| self.0.get(*path) != Some(hash))
+            .map(|(path, _)| path.clone());
+        let deleted = self
+            .0
+            .keys()
+            .filter(|path| !later.0.contains_key(*path))
+            .cloned();
+        modified.chain(deleted).collect()
+    }
+
+    /// The hash of `path` (root-relative); `None` if it didn't exist.
+    pub fn hash_of(&self, path: &Path) -> Option<u64> {
+        self.0.get(path).copied()
+    }
+
+    /// Take `paths` back to how `earlier` found them, after they were restored.
+    pub fn reset(&mut self, paths: &BTreeSet<PathBuf>, earlier: &TreeState) {
+        for path in paths {
+            match earlier.0.get(path) {
+                Some(hash) => self.0.insert(path.clone(), *hash),
+                None => self.0.remove(path),
+            };
+        }
+    }
+}
+
+/// Run `work` (walking, hashing or restoring the tree) on a blocking thread, so reading every
+/// file doesn't stall the async runtime. A panic in it propagates as if it ran inline.
+pub async fn off_runtime<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Files under `root` that aren't ignored, as (root-relative, full) paths. Git internals and
+/// scanner's own state are skipped.
+fn walk(root: &Path) -> impl Iterator<Item = (PathBuf, PathBuf)> {
+    let root = root.to_path_buf();
+    WalkBuilder::new(&root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| {
+            let name = entry.file_name();
+            name != ".git" && name != history::STATE_DIR
+        })
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|ty| ty.is_file()))
+        .filter_map(move |entry| {
+            let rel = entry.path().strip_prefix(&root).ok()?.to_path_buf();
+            Some((rel, entry.into_path()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn restore_undoes_changes_to_the_given_files_only() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "scanner-rs-checkpoint-{}-{nanos}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("src/a.rs"), "a\n").unwrap();
+        std::fs::write(root.join("src/b.rs"), "b\n").unwrap();
+
+        let checkpoint = Checkpoint::capture(&root);
+        let before = TreeState::capture(&root);
+        std::fs::write(root.join("src/a.rs"), "broken\n").unwrap();
+        std::fs::write(root.join("src/b.rs"), "fixed\n").unwrap();
+        std::fs::write(root.join("src/new.rs"), "new\n").unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("target/out"), "ignored\n").unwrap();
+
+        let changed = before.changed(&TreeState::capture(&root));
+        let expected: BTreeSet<PathBuf> = ["src/a.rs", "src/b.rs", "src/new.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(changed, expected);
+
+        let revert: BTreeSet<PathBuf> = ["src/a.rs", "src/new.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        checkpoint.restore(&revert).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("src/a.rs")).unwrap(),
+            "a\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("src/b.rs")).unwrap(),
+            "fixed\n"
+        );
+        assert!(!root.join("src/new.rs").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
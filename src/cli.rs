@@ -1,17 +1,29 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
 
 use crate::Cli;
-use crate::agents::resolve_agent;
+use crate::agents::{resolve_agent, resolve_analyzer};
+use crate::checkpoint::{Checkpoint, TreeState, off_runtime};
 use crate::config;
+use crate::cost::{self, Ledger};
 use crate::demo;
 use crate::error::{CliError, ConfigError};
+use crate::explain;
 use crate::fix;
-use crate::gha;
+use crate::history::{self, HistoryStore};
 use crate::pool::Pool;
+use crate::redact::Redactor;
+use crate::review::{ProposedFix, ReviewReply};
 use crate::runner;
+use crate::snapshot::Snapshot;
 use crate::ui;
+use crate::watch::{WatchSession, WatchTarget};
 
 #[derive(clap::Subcommand, Debug, Clone)]
 pub enum Command {
@@ -20,6 +32,20 @@ pub enum Command {
         /// Check names or tags to run; if omitted, all checks run
         filters: Vec<String>,
     },
+    /// Keep running checks, rerunning the affected ones whenever files change
+    Watch {
+        /// Check names or tags to watch; if omitted, all checks are watched
+        filters: Vec<String>,
+        /// How long (in milliseconds) to collect changes after the first one before rerunning
+        #[arg(long, default_value_t = 300)]
+        debounce_ms: u64,
+    },
+    /// Show the commands and effective environments of setup steps, checks and the agent
+    /// without running anything (secret-looking values are masked)
+    Explain {
+        /// Check names or tags to explain; if omitted, all checks are shown
+        filters: Vec<String>,
+    },
     /// Run a simulated TUI demo (no commands executed)
     Demo {
         /// Disable TUI (headless demo)
@@ -36,90 +62,87 @@ pub async fn run(cli: Cli) -> Result<()> {
         return demo::run_demo(use_tui, use_color).await;
     }
 
-    let config_path = if let Some(cfg) = &cli.config {
-        cfg.clone()
-    } else if let Some(root) = &cli.root {
-        root.join("scanner.toml")
-    } else {
-        PathBuf::from("scanner.toml")
-    };
-
-    let raw = std::fs::read_to_string(&config_path).map_err(|e| ConfigError::ReadFailed {
-        path: config_path.clone(),
-        reason: e.to_string(),
-    })?;
+    if let Some(Command::Watch {
+        filters,
+        debounce_ms,
+    }) = &cli.command
+    {
+        return run_watch(&cli, filters, Duration::from_millis(*debounce_ms)).await;
+    }
 
-    let cfg = config::Config::from_toml(&raw).map_err(|e| ConfigError::ParseFailed {
-        path: config_path.clone(),
-        reason: e.to_string(),
-    })?;
+    let (cfg, root) = load_config(&cli)?;
+
+    if let Some(Command::Explain { filters }) = &cli.command {
+        let checks = runner::select_checks(&cfg, filters, cli.force);
+        let agent = resolve_agent(&cli, &cfg).ok();
+        let analyzer = resolve_analyzer(&cli, &cfg);
+        print!(
+            "{}",
+            explain::explain(&cfg, &checks, agent.as_ref(), analyzer.as_ref())
+        );
+        return Ok(());
+    }
 
-    let root = compute_root(&cli, &config_path)?;
+    // With --snapshot, checks and solvers work on a private copy of the tree.
+    let snapshot = if cli.snapshot {
+        let snapshot_root = root.clone();
+        Some(tokio::task::spawn_blocking(move || Snapshot::create(&snapshot_root)).await??)
+    } else {
+        None
+    };
+    let work_root = snapshot
+        .as_ref()
+        .map(Snapshot::root)
+        .unwrap_or_else(|| root.clone());
 
     let filters = match &cli.command {
         Some(Command::Check { filters }) => filters.clone(),
         None => Vec::new(),
-        Some(Command::Demo { .. }) => unreachable!(),
+        Some(Command::Watch { .. } | Command::Explain { .. } | Command::Demo { .. }) => {
+            unreachable!()
+        }
     };
 
     // Create the shared pool
-    let pool = Pool::new(cli.workers);
+    let pool = Pool::new(cli.workers).with_memory_budget(cfg.memory_budget_mb());
+    let signals = spawn_signal_handler(pool.clone());
 
     let use_tui = cli.tui && atty::is(atty::Stream::Stdout);
     let use_color = !cli.quiet && atty::is(atty::Stream::Stderr);
     let verbose = cli.verbose;
     let (ui_tx, ui_handle) = ui::spawn_ui(use_tui, use_color, verbose, pool.clone());
 
-    let result: Result<()> = async {
-        // Run setup commands first (sequentially)
-        for setup in &cfg.setup {
-            if let Some(tx) = ui_tx.as_ref() {
-                let _ = tx
-                    .send(ui::UiEvent::CheckStarted {
-                        name: format!("setup:{}", setup.name),
-                        desc: Some("Setting up".to_string()),
-                    })
-                    .await;
-            }
-
-            let exit_code = runner::run_setup(setup, &root, ui_tx.clone()).await;
+    let run_options = runner::RunOptions {
+        force: cli.force,
+        quiet: false,
+        fail_fast: cli.fail_fast,
+        keep_going: cli.keep_going,
+        state_root: snapshot.as_ref().map(|_| root.clone()),
+    };
 
-            let success = exit_code == Some(0);
-            if let Some(tx) = ui_tx.as_ref() {
-                let _ = tx
-                    .send(ui::UiEvent::CheckFinished {
-                        name: format!("setup:{}", setup.name),
-                        success,
-                        message: if success {
-                            "done".to_string()
-                        } else {
-                            format!("exit {exit_code:?}")
-                        },
-                        output: None,
-                    })
-                    .await;
-            }
+    // What solvers spend, across all fix rounds.
+    let ledger = Ledger::new(&cfg.costs);
 
-            if !success {
-                return Err(CliError::SetupFailed {
-                    name: setup.name.clone(),
-                    exit_code,
-                }
-                .into());
-            }
-        }
+    let work = async {
+        // Run setup commands first (sequentially)
+        run_setup_steps(&cfg, &work_root, &pool, ui_tx.clone()).await?;
 
         let check_results = runner::run_checks(
             &cfg,
             &filters,
-            cli.force,
+            run_options.clone(),
             &pool,
-            false,
             ui_tx.clone(),
-            &root,
+            &work_root,
         )
         .await;
 
+        if pool.is_cancelled() {
+            return Err(CliError::Cancelled.into());
+        }
+
+        let flaky_checks = track_history(&check_results, &root, ui_tx.as_ref()).await;
+
         if check_results.is_empty() {
             return Err(CliError::NoMatchingChecks {
                 filters: filters.clone(),
@@ -127,15 +150,14 @@ pub async fn run(cli: Cli) -> Result<()> {
             .into());
         }
 
-        let failures: Vec<_> = check_results
-            .iter()
-            .filter(|res| {
-                res.exit_code != Some(0)
-                    || res.annotations.iter().any(|a| gha::is_error_level(a.level))
-            })
-            .collect();
+        // Advisory failures are reported but don't fail the run.
+        let failures: Vec<_> = check_results.iter().filter(|res| res.fails_run()).collect();
+        let fix_advisory = cli.fix_advisory
+            && check_results
+                .iter()
+                .any(|res| res.check.is_advisory() && !res.cancelled && !res.passed());
 
-        if failures.is_empty() {
+        if failures.is_empty() && (!fix_advisory || cli.dry_run) {
             return Ok(());
         }
 
@@ -147,52 +169,227 @@ pub async fn run(cli: Cli) -> Result<()> {
             .into());
         }
 
+        // A run that stopped early asked not to go any further, so nothing gets fixed.
+        if let Some(stopped_by) = run_options.stopped_by(&check_results) {
+            return Err(CliError::ChecksFailed {
+                count: failures.len(),
+                reason: format!(
+                    "stopped early by '{}'; not sending checks to solvers",
+                    stopped_by.check.name
+                ),
+            }
+            .into());
+        }
+
         let agent = resolve_agent(&cli, &cfg)?;
+        if let Some(limit) = agent.max_concurrency {
+            pool.limit_agents(limit);
+        }
 
-        // Group errors by check type
-        let errors_by_check = fix::group_errors_by_check(&check_results);
+        // Group errors by check type; failures of repeatedly flaky checks aren't worth a fix,
+        // and advisory checks only get one when asked for.
+        let fixable_errors = |results: &[runner::CheckResult]| {
+            let fixable_results: Vec<_> = results
+                .iter()
+                .filter(|res| !flaky_checks.contains(&res.check.name))
+                .filter(|res| fix_advisory || !res.check.is_advisory())
+                .cloned()
+                .collect();
+            fix::group_errors_by_check(&fixable_results)
+        };
+        let mut errors_by_check = fixable_errors(&check_results);
         if errors_by_check.is_empty() {
+            if failures.is_empty() {
+                return Ok(());
+            }
+            let reason = if failures
+                .iter()
+                .all(|res| flaky_checks.contains(&res.check.name))
+            {
+                "only repeatedly flaky checks failed; not sending them to solvers"
+            } else if failures.iter().all(|res| res.tool_failed()) {
+                "checks failed with tool errors (error_exit_codes); not sending them to solvers"
+            } else {
+                "no actionable GitHub Actions annotations (configure a formatter or update tool output)"
+            };
             return Err(CliError::ChecksFailed {
                 count: failures.len(),
-                reason: "no actionable GitHub Actions annotations (configure a formatter or update tool output)".to_string(),
+                reason: reason.to_string(),
             }
             .into());
         }
 
-        // Run the solve pipeline: each check gets a single agent run
-        fix::run_fix_pipeline(
-            &agent,
-            &errors_by_check,
-            &pool,
-            &root,
-            ui_tx.clone(),
-        )
-        .await?;
+        // Fix, then rerun the checks that were still failing, until they pass, a round leaves
+        // the same errors behind, or the rounds run out. Each check gets one agent run per round.
+        let redactor = Arc::new(Redactor::new(&cfg.redaction));
+        let review = cli.review || cli.patch_dir.is_some();
+        let settings = fix::FixSettings::new(redactor, review, &cfg.checks)
+            .with_ledger(ledger.clone())
+            .with_analyzer(resolve_analyzer(&cli, &cfg));
+        let max_rounds = cli.fix_rounds as usize;
+        let mut latest = check_results;
+        let mut fingerprints = fix::error_fingerprints(&errors_by_check);
+        let mut round = 1;
+        let mut touched = false;
+        let stalled = loop {
+            if let Some(tx) = ui_tx.as_ref() {
+                let _ = tx
+                    .send(ui::UiEvent::FixRound {
+                        round,
+                        max_rounds,
+                        failing: latest.iter().filter(|res| res.fails_run()).count(),
+                    })
+                    .await;
+            }
+            // Solvers that make things worse get their changes rolled back to this.
+            let tree = work_root.clone();
+            let checkpoint = Arc::new(off_runtime(move || Checkpoint::capture(&tree)).await);
+            let proposed = fix::run_fix_pipeline(
+                &agent,
+                &errors_by_check,
+                &pool,
+                &work_root,
+                ui_tx.clone(),
+                &settings,
+            )
+            .await;
+            if let Some(tx) = ui_tx.as_ref() {
+                let _ = tx.send(ui::UiEvent::SolverCosts(ledger.totals())).await;
+            }
+            let proposed = proposed?;
+            let changes = if review {
+                let patch_dir = cli.patch_dir.as_deref();
+                review_fixes(proposed, &work_root, &root, patch_dir, ui_tx.as_ref()).await?
+            } else {
+                proposed
+                    .into_iter()
+                    .map(|(check, changes)| (check, changes.files))
+                    .collect()
+            };
+
+            touched |= changes.values().any(|files| !files.is_empty());
+            let rerun_names = checks_to_rerun(&latest, &changes)?;
+            let rerun = runner::run_checks(
+                &cfg,
+                &rerun_names,
+                run_options.clone(),
+                &pool,
+                ui_tx.clone(),
+                &work_root,
+            )
+            .await;
+            if pool.is_cancelled() {
+                return Err(CliError::Cancelled.into());
+            }
 
-        // Re-run checks once after fixes
-        let post_results = runner::run_checks(
-            &cfg,
-            &filters,
-            cli.force,
-            &pool,
-            false,
-            ui_tx.clone(),
-            &root,
-        )
-        .await;
+            let regressions = fix::find_regressions(&latest, &rerun, &changes);
+            merge_results(&mut latest, rerun);
+            for (check, solvers) in &regressions.unattributed {
+                if let Some(tx) = ui_tx.as_ref() {
+                    let solvers: Vec<String> =
+                        solvers.iter().map(|s| format!("solve:{s}")).collect();
+                    let _ = tx
+                        .send(ui::UiEvent::CheckFlagged {
+                            name: check.clone(),
+                            reason: format!(
+                                "got worse after changes by {}; can't tell whose, nothing reverted",
+                                solvers.join(", ")
+                            ),
+                        })
+                        .await;
+                }
+            }
+            // The reverted fixes and the checks they broke need another look.
+            let mut recheck = BTreeSet::new();
+            for regression in &regressions.blamed {
+                // Files other solvers changed too can't be rolled back for this one alone.
+                let own = fix::attributed_files(&changes, &regression.solver);
+                let shared = changes[&regression.solver].len() - own.len();
+                let broke = regression.broke.join(", ");
+                let reason = if own.is_empty() {
+                    format!(
+                        "made {broke} worse; nothing reverted: other solvers changed all its files too"
+                    )
+                } else {
+                    let checkpoint = Arc::clone(&checkpoint);
+                    match off_runtime(move || checkpoint.restore(&own)).await {
+                        Ok(()) => {
+                            recheck.insert(regression.solver.clone());
+                            recheck.extend(regression.broke.iter().cloned());
+                            let mut reason = format!("changes reverted: they made {broke} worse");
+                            if shared > 0 {
+                                reason.push_str(&format!(
+                                    "; kept {shared} file(s) other solvers changed as well"
+                                ));
+                            }
+                            reason
+                        }
+                        Err(e) => format!("made {broke} worse; reverting failed: {e:#}"),
+                    }
+                };
+                if let Some(tx) = ui_tx.as_ref() {
+                    let _ = tx
+                        .send(ui::UiEvent::CheckFlagged {
+                            name: format!("solve:{}", regression.solver),
+                            reason,
+                        })
+                        .await;
+                }
+            }
+            if !recheck.is_empty() {
+                let recheck: Vec<String> = recheck.into_iter().collect();
+                let again = runner::run_checks(
+                    &cfg,
+                    &recheck,
+                    run_options.clone(),
+                    &pool,
+                    ui_tx.clone(),
+                    &work_root,
+                )
+                .await;
+                if pool.is_cancelled() {
+                    return Err(CliError::Cancelled.into());
+                }
+                merge_results(&mut latest, again);
+            }
+            errors_by_check = fixable_errors(&latest);
 
-        let remaining: Vec<_> = post_results
-            .iter()
-            .filter(|res| {
-                res.exit_code != Some(0)
-                    || res.annotations.iter().any(|a| gha::is_error_level(a.level))
-            })
-            .collect();
+            let out_of_budget = ledger.exhausted().is_some();
+            if errors_by_check.is_empty() || round == max_rounds || out_of_budget {
+                break false;
+            }
+            let next = fix::error_fingerprints(&errors_by_check);
+            if next == fingerprints {
+                break true;
+            }
+            fingerprints = next;
+            round += 1;
+        };
+
+        // Passes from before the fixes may not hold any more; rerun everything once.
+        if touched {
+            let names: Vec<String> = latest.iter().map(|res| res.check.name.clone()).collect();
+            let last = runner::run_checks(
+                &cfg,
+                &names,
+                run_options.clone(),
+                &pool,
+                ui_tx.clone(),
+                &work_root,
+            )
+            .await;
+            if pool.is_cancelled() {
+                return Err(CliError::Cancelled.into());
+            }
+            merge_results(&mut latest, last);
+        }
+
+        let remaining: Vec<_> = latest.iter().filter(|res| res.fails_run()).collect();
 
         if remaining.is_empty() {
             Ok(())
         } else {
-            let remaining_groups = fix::group_errors_by_check(&post_results);
+            let remaining_groups = fix::group_errors_by_check(&latest);
             let unfixable = remaining
                 .iter()
                 .filter(|res| !remaining_groups.contains_key(&res.check.name))
@@ -204,22 +401,434 @@ pub async fn run(cli: Cli) -> Result<()> {
                     unfixable,
                 }
                 .into())
+            } else if let Some(reason) = ledger.exhausted() {
+                Err(CliError::BudgetExhausted {
+                    count: remaining.len(),
+                    reason,
+                }
+                .into())
+            } else if stalled {
+                Err(CliError::FixesStalled {
+                    count: remaining.len(),
+                    round,
+                }
+                .into())
             } else {
                 Err(CliError::FixesIncomplete {
                     count: remaining.len(),
+                    rounds: round,
                 }
                 .into())
             }
         }
+    };
+    // A second signal abandons the run; dropping it stops whatever it still had running.
+    let result: Result<()> = tokio::select! {
+        result = work => result,
+        _ = signals.forced.cancelled() => Err(CliError::Cancelled.into()),
+    };
+
+    let _ = cost::append_report(&root, &ledger.runs());
+    close_ui(ui_tx, ui_handle, &signals.forced).await;
+
+    if let Some(snapshot) = snapshot {
+        // Diffing runs git and the question waits for the developer; removing the worktree
+        // when the snapshot drops runs git again.
+        let ask = !pool.is_cancelled();
+        let offered =
+            tokio::task::spawn_blocking(move || offer_snapshot_changes(&snapshot, &root, ask))
+                .await?;
+        return result.and(offered);
     }
-    .await;
+    result
+}
 
-    if let Some(tx) = ui_tx {
+/// `--review`: let the developer go through the solvers' patches hunk by hunk and apply the
+/// accepted ones to the root, returning the files each check's accepted fix changed. When
+/// nobody can answer (or with `--patch-dir`) the patches are written out instead.
+async fn review_fixes(
+    proposed: HashMap<String, fix::SolverChanges>,
+    work_root: &Path,
+    root: &Path,
+    patch_dir: Option<&Path>,
+    ui_tx: Option<&Sender<ui::UiEvent>>,
+) -> Result<HashMap<String, BTreeSet<PathBuf>>> {
+    let mut fixes: Vec<ProposedFix> = proposed
+        .iter()
+        .filter_map(|(check, changes)| Some(ProposedFix::parse(check, changes.patch.as_ref()?)))
+        .filter(|fix| !fix.files.is_empty())
+        .collect();
+    fixes.sort_by(|a, b| a.check.cmp(&b.check));
+    if fixes.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let tx = match ui_tx {
+        Some(tx) if patch_dir.is_none() && atty::is(atty::Stream::Stdin) => tx,
+        _ => {
+            let dir = match patch_dir {
+                Some(dir) => dir.to_path_buf(),
+                None => {
+                    let state_dir = root.join(history::STATE_DIR);
+                    history::ensure_state_dir(&state_dir)?;
+                    state_dir.join("patches")
+                }
+            };
+            crate::review::write_patches(&dir, &fixes)?;
+            return Err(CliError::FixesProposed {
+                count: fixes.len(),
+                dir,
+            }
+            .into());
+        }
+    };
+
+    let (reply, answer) = ReviewReply::channel();
+    let _ = tx.send(ui::UiEvent::Review { fixes, reply }).await;
+    // A UI that goes away without answering rejects everything.
+    let reviewed = answer.await.unwrap_or_default();
+
+    let mut changes = HashMap::new();
+    for fix in &reviewed {
+        let tree = work_root.to_path_buf();
+        let before = off_runtime(move || TreeState::capture(&tree)).await;
+        match fix.apply(work_root) {
+            Ok(false) => {}
+            Ok(true) => {
+                let tree = work_root.to_path_buf();
+                let changed = before.changed(&off_runtime(move || TreeState::capture(&tree)).await);
+                changes.insert(fix.check.clone(), changed);
+            }
+            Err(err) => {
+                let _ = tx
+                    .send(ui::UiEvent::CheckFlagged {
+                        name: format!("solve:{}", fix.check),
+                        reason: format!("accepted changes not applied: {err:#}"),
+                    })
+                    .await;
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Checks to run again after a fix round: those that didn't pass, and passing ones whose
+/// `watch` globs match a file the solvers changed (any change, without globs).
+fn checks_to_rerun(
+    latest: &[runner::CheckResult],
+    changes: &HashMap<String, BTreeSet<PathBuf>>,
+) -> Result<Vec<String>> {
+    let changed: BTreeSet<PathBuf> = changes.values().flatten().cloned().collect();
+    let mut names = Vec::new();
+    for result in latest {
+        if !result.passed() || WatchTarget::new(result.check.clone())?.is_affected_by(&changed) {
+            names.push(result.check.name.clone());
+        }
+    }
+    Ok(names)
+}
+
+/// Replace the results of checks that ran again.
+fn merge_results(latest: &mut Vec<runner::CheckResult>, results: Vec<runner::CheckResult>) {
+    for result in results {
+        match latest
+            .iter_mut()
+            .find(|res| res.check.name == result.check.name)
+        {
+            Some(slot) => *slot = result,
+            None => latest.push(result),
+        }
+    }
+}
+
+/// Show what changed inside the snapshot and apply it to the working copy once the developer
+/// agrees. The patch is saved under `.scanner/` either way, so it can be applied later.
+fn offer_snapshot_changes(snapshot: &Snapshot, root: &Path, ask: bool) -> Result<()> {
+    let patch = snapshot.diff()?;
+    if patch.is_empty() {
+        eprintln!("snapshot: no changes");
+        return Ok(());
+    }
+    let dir = root.join(history::STATE_DIR);
+    history::ensure_state_dir(&dir)?;
+    let patch_path = dir.join("snapshot.patch");
+    std::fs::write(&patch_path, &patch)
+        .with_context(|| format!("failed to write {}", patch_path.display()))?;
+
+    eprintln!("Changes made in the snapshot:\n{}", snapshot.diff_stat()?);
+    let interactive = ask && atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr);
+    if interactive && confirm("Apply them to your working copy? [y/N] ")? {
+        snapshot.apply(&patch)?;
+        let _ = std::fs::remove_file(&patch_path);
+        eprintln!("Applied.");
+    } else {
+        eprintln!(
+            "Not applied. The patch is saved at {}; apply it with `git -C {} apply {}`.",
+            patch_path.display(),
+            snapshot.repo().display(),
+            patch_path.display()
+        );
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question}");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Watch mode: keep the UI open and rerun affected checks on file changes.
+async fn run_watch(cli: &Cli, filters: &[String], debounce: Duration) -> Result<()> {
+    if cli.snapshot {
+        return Err(CliError::SnapshotInWatch.into());
+    }
+    let (cfg, root) = load_config(cli)?;
+
+    let checks = runner::select_checks(&cfg, filters, cli.force);
+    if checks.is_empty() {
+        return Err(CliError::NoMatchingChecks {
+            filters: filters.to_vec(),
+        }
+        .into());
+    }
+    let session = WatchSession::new(checks, &cfg, &root)?;
+
+    let pool = Pool::new(cli.workers).with_memory_budget(cfg.memory_budget_mb());
+    let signals = spawn_signal_handler(pool.clone());
+
+    // Watch mode is meant to live in a terminal split, so the TUI is the default.
+    let use_tui = atty::is(atty::Stream::Stdout);
+    let use_color = !cli.quiet && atty::is(atty::Stream::Stderr);
+    let (ui_tx, ui_handle) = ui::spawn_ui(use_tui, use_color, cli.verbose, pool.clone());
+
+    let setup = tokio::select! {
+        result = run_setup_steps(&cfg, &root, &pool, ui_tx.clone()) => result,
+        _ = signals.forced.cancelled() => Err(CliError::Cancelled.into()),
+    };
+    if let Err(err) = setup {
+        close_ui(ui_tx, ui_handle, &signals.forced).await;
+        return Err(err);
+    }
+
+    // The session hands the UI back unless the developer already quit it.
+    if let Some(ui_handle) = session.run(&pool, debounce, ui_tx.clone(), ui_handle).await {
+        close_ui(ui_tx, ui_handle, &signals.forced).await;
+    }
+    // Quitting the TUI is the normal way out of watch mode; only a signal counts as cancelled.
+    if signals.first.is_cancelled() {
+        return Err(CliError::Cancelled.into());
+    }
+    Ok(())
+}
+
+/// Tell the UI the work is over and wait for it to restore the terminal. After a second
+/// signal it closes right away instead of waiting for the developer to leave the TUI.
+async fn close_ui(
+    ui_tx: Option<Sender<ui::UiEvent>>,
+    mut ui_handle: tokio::task::JoinHandle<()>,
+    forced: &CancellationToken,
+) {
+    let Some(tx) = ui_tx else {
+        let _ = ui_handle.await;
+        return;
+    };
+    if !forced.is_cancelled() {
         let _ = tx.send(ui::UiEvent::Done).await;
+        tokio::select! {
+            _ = &mut ui_handle => return,
+            _ = forced.cancelled() => {}
+        }
     }
+    let _ = tx.send(ui::UiEvent::Exit).await;
     let _ = ui_handle.await;
+}
 
-    result
+/// What the signal handler has seen.
+struct Signals {
+    /// Cancelled on the first SIGINT/SIGTERM, along with the pool.
+    first: CancellationToken,
+    /// Cancelled on the second one: the run is abandoned and its work dropped.
+    forced: CancellationToken,
+}
+
+/// Cancel the pool on SIGINT/SIGTERM so running checks and agents are stopped cleanly.
+/// A second signal abandons the run; it still returns, so the terminal gets restored and
+/// drop guards (process groups, worktrees, temp files) still run.
+fn spawn_signal_handler(pool: Pool) -> Signals {
+    let signals = Signals {
+        first: CancellationToken::new(),
+        forced: CancellationToken::new(),
+    };
+    let first = signals.first.clone();
+    let forced = signals.forced.clone();
+    tokio::spawn(async move {
+        while wait_for_signal().await {
+            if first.is_cancelled() {
+                forced.cancel();
+                break;
+            }
+            first.cancel();
+            pool.cancel();
+        }
+    });
+    signals
+}
+
+/// Wait for SIGINT (Ctrl+C) or, on Unix, SIGTERM. Returns false if signals can't be observed.
+async fn wait_for_signal() -> bool {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let Ok(mut term) = signal(SignalKind::terminate()) else {
+            return tokio::signal::ctrl_c().await.is_ok();
+        };
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res.is_ok(),
+            _ = term.recv() => true,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.is_ok()
+    }
+}
+
+fn load_config(cli: &Cli) -> Result<(config::Config, PathBuf)> {
+    let config_path = if let Some(cfg) = &cli.config {
+        cfg.clone()
+    } else if let Some(root) = &cli.root {
+        root.join("scanner.toml")
+    } else {
+        PathBuf::from("scanner.toml")
+    };
+
+    let raw = std::fs::read_to_string(&config_path).map_err(|e| ConfigError::ReadFailed {
+        path: config_path.clone(),
+        reason: e.to_string(),
+    })?;
+
+    let cfg = config::Config::from_toml(&raw).map_err(|e| ConfigError::ParseFailed {
+        path: config_path.clone(),
+        reason: e.to_string(),
+    })?;
+
+    let root = compute_root(cli, &config_path)?;
+    Ok((cfg, root))
+}
+
+/// Record this run's durations and flaky checks in the local history, flagging repeat
+/// offenders and significant slowdowns.
+///
+/// Returns the names of checks that have been flaky repeatedly.
+async fn track_history(
+    results: &[runner::CheckResult],
+    root: &Path,
+    ui_tx: Option<&Sender<ui::UiEvent>>,
+) -> HashSet<String> {
+    let mut history = HistoryStore::load(root);
+    let mut flags = Vec::new();
+    for result in results.iter().filter(|r| !r.cancelled) {
+        if let Some(median) = history.regression(&result.check.name, result.duration) {
+            flags.push((
+                result.check.name.clone(),
+                format!(
+                    "took {}, usually {}",
+                    ui::format_duration(result.duration),
+                    ui::format_duration(median)
+                ),
+            ));
+        }
+    }
+    history.record(results);
+    // History is a convenience; failing to persist it must not fail the run.
+    let _ = history.save();
+
+    let mut flaky = HashSet::new();
+    for result in results {
+        let name = &result.check.name;
+        if history.is_repeatedly_flaky(name) {
+            flaky.insert(name.clone());
+            flags.push((
+                name.clone(),
+                format!("flaky in {} recent runs", history.flaky_count(name)),
+            ));
+        }
+    }
+
+    if let Some(tx) = ui_tx {
+        for (name, reason) in flags {
+            let _ = tx.send(ui::UiEvent::CheckFlagged { name, reason }).await;
+        }
+    }
+    flaky
+}
+
+/// Run setup commands sequentially, stopping at the first failure.
+async fn run_setup_steps(
+    cfg: &config::Config,
+    root: &Path,
+    pool: &Pool,
+    ui_tx: Option<Sender<ui::UiEvent>>,
+) -> Result<()> {
+    let cancel = pool.cancel_token();
+    let redactor = Arc::new(Redactor::new(&cfg.redaction));
+    for setup in &cfg.setup {
+        if cancel.is_cancelled() {
+            return Err(CliError::Cancelled.into());
+        }
+        if let Some(tx) = ui_tx.as_ref() {
+            let _ = tx
+                .send(ui::UiEvent::CheckStarted {
+                    name: format!("setup:{}", setup.name),
+                    desc: Some("Setting up".to_string()),
+                    expected: None,
+                })
+                .await;
+        }
+
+        let exit_code =
+            runner::run_setup(setup, root, &cancel, ui_tx.clone(), redactor.clone()).await;
+        if cancel.is_cancelled() {
+            if let Some(tx) = ui_tx.as_ref() {
+                let _ = tx
+                    .send(ui::UiEvent::CheckCancelled {
+                        name: format!("setup:{}", setup.name),
+                    })
+                    .await;
+            }
+            return Err(CliError::Cancelled.into());
+        }
+
+        let success = exit_code == Some(0);
+        if let Some(tx) = ui_tx.as_ref() {
+            let _ = tx
+                .send(ui::UiEvent::CheckFinished {
+                    name: format!("setup:{}", setup.name),
+                    success,
+                    message: if success {
+                        "done".to_string()
+                    } else {
+                        format!("exit {exit_code:?}")
+                    },
+                    output: None,
+                    flaky: false,
+                    advisory: false,
+                    log_path: None,
+                })
+                .await;
+        }
+
+        if !success {
+            return Err(CliError::SetupFailed {
+                name: setup.name.clone(),
+                exit_code,
+            }
+            .into());
+        }
+    }
+    Ok(())
 }
 
 fn compute_root(cli: &Cli, config_path: &Path) -> Result<PathBuf> {
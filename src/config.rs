@@ -1,7 +1,10 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
 use serde::Deserialize;
 
 use crate::error::ConfigError;
@@ -16,6 +19,8 @@ struct RawSetup {
     #[serde(default)]
     timeout: Option<u64>,
     #[serde(default)]
+    kill_grace: Option<u64>,
+    #[serde(default)]
     cwd: Option<String>,
 }
 
@@ -32,6 +37,8 @@ struct RawCheck {
     #[serde(default)]
     timeout: Option<u64>,
     #[serde(default)]
+    kill_grace: Option<u64>,
+    #[serde(default)]
     enabled: Option<bool>,
     #[serde(default)]
     tags: Vec<String>,
@@ -41,6 +48,38 @@ struct RawCheck {
     cwd: Option<String>,
     #[serde(default)]
     lock: Option<String>,
+    #[serde(default, alias = "cpus")]
+    weight: Option<u32>,
+    #[serde(default)]
+    memory: Option<RawSize>,
+    #[serde(default)]
+    watch: Vec<String>,
+    #[serde(default)]
+    retries: u32,
+    #[serde(default)]
+    retry_on: Vec<String>,
+    #[serde(default)]
+    critical: bool,
+    #[serde(default)]
+    pty: Option<RawPty>,
+    #[serde(default)]
+    success_exit_codes: Option<Vec<i32>>,
+    #[serde(default)]
+    error_exit_codes: Vec<i32>,
+    #[serde(default)]
+    fail_if_output_matches: Vec<String>,
+    #[serde(default)]
+    mode: CheckMode,
+    #[serde(default)]
+    inherit_env: Option<bool>,
+    #[serde(default)]
+    env_passthrough: Vec<String>,
+    #[serde(default)]
+    fix_scope: Option<RawFixScope>,
+    #[serde(default)]
+    on_scope_violation: ScopeViolation,
+    #[serde(default)]
+    prompt: Option<RawPrompt>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -50,6 +89,20 @@ pub struct RawAgent {
     pub env: HashMap<String, String>,
     #[serde(default)]
     pub timeout: Option<u64>,
+    #[serde(default)]
+    pub kill_grace: Option<u64>,
+    #[serde(default)]
+    pub clear_env: Option<bool>,
+    #[serde(default)]
+    pub env_passthrough: Vec<String>,
+    #[serde(default)]
+    pub output: AgentOutput,
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    pub rate_limit_retries: Option<u32>,
+    #[serde(default)]
+    pub rate_limit_backoff: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -60,8 +113,153 @@ pub struct RawAgents {
     pub fixer: Option<RawAgent>,
 }
 
+/// A size: a plain number (in a unit that depends on the setting) or a string such as
+/// `"512K"`, `"2G"` or `"300B"`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum RawSize {
+    Number(u64),
+    Text(String),
+}
+
+const MB: u64 = 1024 * 1024;
+
+impl RawSize {
+    /// Size in bytes; plain numbers (with or without a `B` suffix) count in `unit` bytes.
+    fn to_bytes(&self, unit: u64) -> std::result::Result<u64, String> {
+        let text = match self {
+            RawSize::Number(n) => return Ok(n.saturating_mul(unit)),
+            RawSize::Text(text) => text.trim(),
+        };
+        let upper = text.to_ascii_uppercase();
+        let explicit_bytes = upper.ends_with('B') && !upper.ends_with("IB");
+        let digits = upper.trim_end_matches('B').trim_end_matches('I').trim_end();
+        let (number, multiplier) = match digits.chars().last() {
+            Some('K') => (&digits[..digits.len() - 1], 1024),
+            Some('M') => (&digits[..digits.len() - 1], MB),
+            Some('G') => (&digits[..digits.len() - 1], 1024 * MB),
+            Some('T') => (&digits[..digits.len() - 1], 1024 * 1024 * MB),
+            _ if explicit_bytes => (digits, 1),
+            _ => (digits, unit),
+        };
+        let value: u64 = number
+            .trim()
+            .parse()
+            .map_err(|_| "expected a size such as 512, \"512M\" or \"2G\"".to_string())?;
+        Ok(value.saturating_mul(multiplier))
+    }
+
+    /// Size in whole megabytes (rounded up); plain numbers are megabytes.
+    fn to_mb(&self) -> std::result::Result<u64, String> {
+        Ok(self.to_bytes(MB)?.div_ceil(MB))
+    }
+}
+
+/// `pty = true`, or `pty = { columns = 200, rows = 50 }` to pick the terminal size.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawPty {
+    Enabled(bool),
+    Size {
+        #[serde(default)]
+        columns: Option<u16>,
+        #[serde(default)]
+        rows: Option<u16>,
+    },
+}
+
+/// `[prompt]` globally, `prompt = { ... }` per check.
+#[derive(Debug, Deserialize, Clone, Default)]
+struct RawPrompt {
+    #[serde(default)]
+    template: Option<String>,
+    #[serde(default)]
+    template_file: Option<PathBuf>,
+    #[serde(default)]
+    context_lines: Option<usize>,
+    #[serde(default)]
+    instructions: Vec<PathBuf>,
+}
+
+/// `fix_scope`: `"annotated"`, `"directory"`, `"any"`, or a list of globs.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+enum RawFixScope {
+    Keyword(String),
+    Globs(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RawPool {
+    #[serde(default)]
+    memory: Option<RawSize>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RawCosts {
+    #[serde(default)]
+    max_cost: Option<f64>,
+    #[serde(default)]
+    max_tokens: Option<u64>,
+    #[serde(default)]
+    prices: HashMap<String, RawPrice>,
+}
+
+/// USD per million tokens.
+#[derive(Debug, Deserialize)]
+struct RawPrice {
+    input: f64,
+    output: f64,
+    #[serde(default)]
+    cached_input: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RawOutput {
+    #[serde(default)]
+    max_bytes: Option<RawSize>,
+    #[serde(default)]
+    keep_logs: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RawRedaction {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    high_entropy: Option<bool>,
+    #[serde(default)]
+    patterns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLock {
+    #[serde(default = "default_lock_capacity")]
+    capacity: usize,
+}
+
+fn default_lock_capacity() -> usize {
+    1
+}
+
 #[derive(Debug, Deserialize)]
 struct RawConfig {
+    #[serde(default)]
+    inherit_env: Option<bool>,
+    #[serde(default)]
+    env_passthrough: Vec<String>,
+    #[serde(default)]
+    pool: RawPool,
+    #[serde(default)]
+    output: RawOutput,
+    #[serde(default)]
+    redaction: RawRedaction,
+    #[serde(default)]
+    prompt: RawPrompt,
+    #[serde(default)]
+    costs: RawCosts,
+    #[serde(default)]
+    locks: HashMap<String, RawLock>,
     #[serde(default)]
     setup: Vec<RawSetup>,
     #[serde(default)]
@@ -72,6 +270,22 @@ struct RawConfig {
     agents: RawAgents,
 }
 
+/// Size of the pseudo-terminal a check runs under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtySize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl Default for PtySize {
+    fn default() -> Self {
+        Self {
+            columns: 120,
+            rows: 40,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CommandSpec {
     pub program: String,
@@ -85,7 +299,10 @@ pub struct Setup {
     pub command: CommandSpec,
     pub env: HashMap<String, String>,
     pub timeout: Option<Duration>,
+    /// Grace period between SIGTERM and SIGKILL when the command must be stopped.
+    pub kill_grace: Option<Duration>,
     pub cwd: Option<String>,
+    pub env_policy: EnvPolicy,
 }
 
 #[derive(Debug, Clone)]
@@ -96,12 +313,215 @@ pub struct Check {
     pub fixer: Option<CommandSpec>,
     pub env: HashMap<String, String>,
     pub timeout: Option<Duration>,
+    /// Grace period between SIGTERM and SIGKILL when the check must be stopped.
+    pub kill_grace: Option<Duration>,
     pub enabled: bool,
     pub tags: Vec<String>,
     pub description: Option<String>,
     pub cwd: Option<String>,
     /// Optional lock group name to serialize checks that contend for a shared resource.
     pub lock: Option<String>,
+    /// Number of pool slots the check occupies while running (`weight` or `cpus`).
+    pub weight: u32,
+    /// Memory (MB) reserved from the pool's memory budget while the check runs.
+    pub memory_mb: Option<u64>,
+    /// Glob patterns (relative to the root) that trigger this check in watch mode.
+    /// Empty means any non-ignored change triggers it.
+    pub watch: Vec<String>,
+    /// Extra attempts allowed after a failing run.
+    pub retries: u32,
+    /// Failures that qualify for a retry; empty means any failure does.
+    pub retry_on: Vec<RetryCondition>,
+    /// A failure of this check stops the rest of the run (as with `--fail-fast`).
+    pub critical: bool,
+    /// Run under a pseudo-terminal of this size (stdout and stderr merged).
+    pub pty: Option<PtySize>,
+    /// Exit codes that count as success (`[0]` by default).
+    pub success_exit_codes: Vec<i32>,
+    /// Exit codes meaning the tool itself broke (crash, bad usage) rather than reporting
+    /// findings. Such failures are never sent to solvers.
+    pub error_exit_codes: Vec<i32>,
+    /// The check fails when its output matches any of these, whatever the exit code.
+    pub fail_if_output_matches: Vec<Regex>,
+    pub mode: CheckMode,
+    /// Which of scanner's own environment variables the check sees.
+    pub env_policy: EnvPolicy,
+    /// Files a solver fixing this check may change.
+    pub fix_scope: FixScope,
+    /// What happens to solver changes outside `fix_scope`.
+    pub on_scope_violation: ScopeViolation,
+    /// What solvers fixing this check are told (global `[prompt]` refined per check).
+    pub prompt: PromptConfig,
+}
+
+impl Check {
+    /// The environment the check's commands run with.
+    pub fn environment(&self) -> Environment {
+        self.env_policy.resolve(&self.env)
+    }
+
+    /// The command exited with one of the success exit codes.
+    pub fn exit_succeeded(&self, exit_code: Option<i32>) -> bool {
+        exit_code.is_some_and(|code| self.success_exit_codes.contains(&code))
+    }
+
+    /// Failures of this check are warnings only (`mode = "advisory"`).
+    pub fn is_advisory(&self) -> bool {
+        self.mode == CheckMode::Advisory
+    }
+
+    /// The command exited with one of the error exit codes (the tool failed).
+    pub fn tool_failed(&self, exit_code: Option<i32>) -> bool {
+        exit_code.is_some_and(|code| self.error_exit_codes.contains(&code))
+    }
+}
+
+/// Whether a check's failures fail the run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckMode {
+    /// Failures fail the run (the default).
+    #[default]
+    Blocking,
+    /// Failures are shown and reported but don't fail the run or go to solvers
+    /// (unless `--fix-advisory`).
+    Advisory,
+}
+
+/// Which files a check's solver may change.
+#[derive(Debug, Clone, Default)]
+pub enum FixScope {
+    /// Any file (`"any"`, the default).
+    #[default]
+    Any,
+    /// Only files named in the errors' annotations (`"annotated"`).
+    Annotated,
+    /// Files in the directory of a file named in the annotations (`"directory"`).
+    Directory,
+    /// Files matching any of these globs, relative to the root.
+    Globs(GlobSet),
+}
+
+impl FixScope {
+    fn parse(raw: RawFixScope) -> std::result::Result<Self, String> {
+        let globs = match raw {
+            RawFixScope::Keyword(keyword) => {
+                return match keyword.as_str() {
+                    "any" => Ok(Self::Any),
+                    "annotated" => Ok(Self::Annotated),
+                    "directory" => Ok(Self::Directory),
+                    _ => Err(format!(
+                        "unknown scope '{keyword}'; expected annotated, directory, any or globs"
+                    )),
+                };
+            }
+            RawFixScope::Globs(globs) => globs,
+        };
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &globs {
+            builder.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+        }
+        builder.build().map(Self::Globs).map_err(|e| e.to_string())
+    }
+
+    /// Whether a solver may change `path` (root-relative), given the root-relative files
+    /// named in the errors it was fixing.
+    pub fn allows(&self, path: &Path, annotated: &[PathBuf]) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Annotated => annotated.iter().any(|file| file == path),
+            Self::Directory => annotated.iter().any(|file| file.parent() == path.parent()),
+            Self::Globs(globs) => globs.is_match(path),
+        }
+    }
+}
+
+/// How the task sent to solvers is worded and what context comes with it.
+#[derive(Debug, Clone, Default)]
+pub struct PromptConfig {
+    /// Replaces the built-in task description; `None` keeps it.
+    pub template: Option<PromptTemplate>,
+    /// Source lines included above and below each annotated line (0 for none).
+    pub context_lines: usize,
+    /// Files (relative to the root) whose content is included, e.g. `AGENTS.md`.
+    pub instructions: Vec<PathBuf>,
+}
+
+/// A prompt template, inline or read from a file (relative to the root) when used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptTemplate {
+    Inline(String),
+    File(PathBuf),
+}
+
+impl PromptConfig {
+    /// `raw` on top of `base`: a template or context size replaces the base one, and
+    /// instructions files add up.
+    fn refine(&self, raw: RawPrompt, owner: &str) -> std::result::Result<Self, ConfigError> {
+        let template = match (raw.template, raw.template_file) {
+            (Some(_), Some(_)) => {
+                return Err(ConfigError::ConflictingPromptTemplate {
+                    owner: owner.to_string(),
+                });
+            }
+            (Some(text), None) => Some(PromptTemplate::Inline(text)),
+            (None, Some(path)) => Some(PromptTemplate::File(path)),
+            (None, None) => self.template.clone(),
+        };
+        Ok(Self {
+            template,
+            context_lines: raw.context_lines.unwrap_or(self.context_lines),
+            instructions: self
+                .instructions
+                .iter()
+                .cloned()
+                .chain(raw.instructions)
+                .collect(),
+        })
+    }
+}
+
+/// What happens to solver changes outside a check's `fix_scope`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeViolation {
+    /// The out-of-scope files are put back (the default).
+    #[default]
+    Revert,
+    /// The changes are kept, and the solver is flagged.
+    Flag,
+}
+
+/// A failure condition that makes a check eligible for a retry.
+#[derive(Debug, Clone)]
+pub enum RetryCondition {
+    /// The check timed out (`"timeout"`).
+    Timeout,
+    /// The check exited with this code (`"exit:137"`).
+    ExitCode(i32),
+    /// The check's output matches this regex (`"output:connection reset"`).
+    Output(Regex),
+}
+
+impl RetryCondition {
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        if value == "timeout" {
+            return Ok(Self::Timeout);
+        }
+        if let Some(code) = value.strip_prefix("exit:") {
+            return code
+                .trim()
+                .parse()
+                .map(Self::ExitCode)
+                .map_err(|_| format!("invalid exit code '{code}'"));
+        }
+        if let Some(pattern) = value.strip_prefix("output:") {
+            return Regex::new(pattern)
+                .map(Self::Output)
+                .map_err(|e| e.to_string());
+        }
+        Err("expected \"timeout\", \"exit:<code>\" or \"output:<regex>\"".to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -109,6 +529,180 @@ pub struct Agent {
     pub command: CommandSpec,
     pub env: HashMap<String, String>,
     pub timeout: Option<Duration>,
+    /// Grace period between SIGTERM and SIGKILL when the agent must be stopped.
+    pub kill_grace: Option<Duration>,
+    /// Agents start from a cleared environment unless `clear_env = false`.
+    pub env_policy: EnvPolicy,
+    /// How the agent's stdout is read.
+    pub output: AgentOutput,
+    /// Agents running at once, on slots of their own; `None` shares the pool with checks.
+    pub max_concurrency: Option<usize>,
+    /// Retries of a run the provider turned away with a rate limit.
+    pub rate_limit_retries: u32,
+    /// Wait before the first such retry; it doubles with each further one.
+    pub rate_limit_backoff: Duration,
+}
+
+/// Retries of rate-limited agent runs unless `rate_limit_retries` says otherwise.
+pub const DEFAULT_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// First wait after a rate limit unless `rate_limit_backoff` says otherwise.
+pub const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(20);
+
+impl Agent {
+    /// The model named on the command line (`--model`, `-m`), if any.
+    pub fn model(&self) -> Option<&str> {
+        let mut args = self.command.args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(model) = arg.strip_prefix("--model=") {
+                return Some(model);
+            }
+            if arg == "--model" || arg == "-m" {
+                return args.next().map(String::as_str);
+            }
+        }
+        None
+    }
+}
+
+/// The shape of an agent's stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AgentOutput {
+    /// Told apart by the command: `codex ... --json` or `claude ... --output-format
+    /// stream-json`; anything else is text (the default).
+    #[default]
+    Auto,
+    /// Plain text, kept as the solver's explanation.
+    Text,
+    /// `codex exec --json` events.
+    CodexJson,
+    /// `claude --output-format stream-json` messages.
+    ClaudeStreamJson,
+}
+
+impl AgentOutput {
+    /// `Auto` resolved for `command`.
+    pub fn resolve(self, command: &CommandSpec) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        let program = Path::new(&command.program)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let has = |arg: &str| command.args.iter().any(|a| a == arg);
+        match program {
+            "codex" if has("--json") => Self::CodexJson,
+            "claude" if has("stream-json") => Self::ClaudeStreamJson,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Variables kept even when the environment is cleared; without them most tools can't run.
+const BASELINE_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LC_*", "TMPDIR", "TZ",
+];
+
+/// Also kept for agents: their own credentials and network settings.
+pub const AGENT_BASELINE_ENV: &[&str] = &[
+    "OPENAI_*",
+    "ANTHROPIC_*",
+    "CODEX_*",
+    "CLAUDE_*",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+    "SSL_CERT_FILE",
+    "SSL_CERT_DIR",
+];
+
+/// Which of scanner's own environment variables a command sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvPolicy {
+    /// Pass scanner's whole environment through.
+    pub inherit: bool,
+    /// Otherwise, the variables passed through on top of the baseline: exact names or
+    /// prefixes ending in `*`.
+    pub passthrough: Vec<String>,
+}
+
+impl Default for EnvPolicy {
+    fn default() -> Self {
+        Self {
+            inherit: true,
+            passthrough: Vec::new(),
+        }
+    }
+}
+
+impl EnvPolicy {
+    /// A cleared environment that keeps only the baseline and `passthrough`.
+    pub fn cleared(passthrough: Vec<String>) -> Self {
+        Self {
+            inherit: false,
+            passthrough,
+        }
+    }
+
+    /// Whether a variable of scanner's environment is passed through.
+    pub fn allows(&self, name: &str) -> bool {
+        self.inherit
+            || BASELINE_ENV
+                .iter()
+                .copied()
+                .chain(self.passthrough.iter().map(String::as_str))
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == pattern,
+                })
+    }
+
+    /// The environment for a command with its own `vars`, given scanner's environment.
+    pub fn resolve_with(
+        &self,
+        vars: &HashMap<String, String>,
+        parent: impl IntoIterator<Item = (String, String)>,
+    ) -> Environment {
+        if self.inherit {
+            return Environment {
+                clear: false,
+                vars: vars.clone(),
+            };
+        }
+        let mut resolved: HashMap<String, String> = parent
+            .into_iter()
+            .filter(|(name, _)| self.allows(name))
+            .collect();
+        resolved.extend(vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Environment {
+            clear: true,
+            vars: resolved,
+        }
+    }
+
+    /// The environment for a command with its own `vars`.
+    pub fn resolve(&self, vars: &HashMap<String, String>) -> Environment {
+        self.resolve_with(vars, scanner_env())
+    }
+}
+
+/// Scanner's own environment, skipping variables that aren't valid Unicode.
+pub fn scanner_env() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os().filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+}
+
+/// The environment a command runs with.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    /// Start from an empty environment instead of scanner's own.
+    pub clear: bool,
+    /// Variables set on top.
+    pub vars: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -117,8 +711,88 @@ pub struct Agents {
     pub fixer: Option<Agent>,
 }
 
+/// Global pool limits.
+#[derive(Debug, Clone, Default)]
+pub struct PoolConfig {
+    /// Memory budget (MB) shared by checks that declare a `memory` hint.
+    pub memory_mb: Option<u64>,
+}
+
+/// Solver spending: prices to estimate it with and limits per run.
+#[derive(Debug, Clone, Default)]
+pub struct CostConfig {
+    /// No new solvers start once the run's estimated cost (USD) reaches this.
+    pub max_cost: Option<f64>,
+    /// No new solvers start once the run's tokens (input and output) reach this.
+    pub max_tokens: Option<u64>,
+    /// Prices by model name, on top of the built-in ones.
+    pub prices: HashMap<String, Price>,
+}
+
+/// What a model costs, in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    /// Input served from the provider's cache; billed as plain input when not given.
+    pub cached_input: f64,
+    pub output: f64,
+}
+
+/// Default in-memory cap for each output stream of a check.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Default number of per-run log directories kept under `.scanner/logs`.
+pub const DEFAULT_KEEP_LOGS: usize = 10;
+
+/// Limits on how much check output is kept in memory and on disk.
+#[derive(Debug, Clone)]
+pub struct OutputConfig {
+    /// Bytes of each stream kept in memory (head and tail); the rest only goes to the log.
+    pub max_bytes: usize,
+    /// Number of per-run log directories to keep.
+    pub keep_logs: usize,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            keep_logs: DEFAULT_KEEP_LOGS,
+        }
+    }
+}
+
+/// What is masked in check output and annotations before they are shown, logged or sent
+/// to an agent.
+#[derive(Debug, Clone)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Also mask long random-looking strings that no detector recognises.
+    pub high_entropy: bool,
+    /// Extra patterns; a `secret` group limits the masking to that part of the match.
+    pub patterns: Vec<Regex>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            high_entropy: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// Global environment policy; checks and agents refine it.
+    pub env_policy: EnvPolicy,
+    pub pool: PoolConfig,
+    pub output: OutputConfig,
+    pub redaction: RedactionConfig,
+    pub costs: CostConfig,
+    /// Capacity of each lock group (`[locks.<name>]`); undeclared groups hold one check.
+    pub locks: HashMap<String, usize>,
     pub setup: Vec<Setup>,
     pub checks: Vec<Check>,
     pub agent: Option<Agent>,
@@ -126,9 +800,81 @@ pub struct Config {
 }
 
 impl Config {
+    /// The memory budget that checks' `memory` hints reserve from: `[pool] memory`, or when
+    /// only hints are given, the machine's physical memory, so they still keep memory-hungry
+    /// checks from all starting at once.
+    pub fn memory_budget_mb(&self) -> Option<u64> {
+        self.pool.memory_mb.or_else(|| {
+            self.checks
+                .iter()
+                .any(|check| check.memory_mb.is_some())
+                .then(crate::pool::system_memory_mb)
+                .flatten()
+        })
+    }
+
     pub fn from_toml(input: &str) -> Result<Self> {
         let raw: RawConfig = toml::from_str(input)?;
 
+        let pool = PoolConfig {
+            memory_mb: raw
+                .pool
+                .memory
+                .as_ref()
+                .map(|memory| {
+                    memory.to_mb().map_err(|reason| ConfigError::InvalidMemory {
+                        owner: "pool".to_string(),
+                        reason,
+                    })
+                })
+                .transpose()?,
+        };
+
+        let output = OutputConfig {
+            max_bytes: match raw.output.max_bytes.as_ref() {
+                Some(size) => {
+                    let bytes = size
+                        .to_bytes(1)
+                        .map_err(|reason| ConfigError::InvalidSize {
+                            setting: "output.max_bytes".to_string(),
+                            reason,
+                        })?;
+                    usize::try_from(bytes).unwrap_or(usize::MAX)
+                }
+                None => DEFAULT_MAX_OUTPUT_BYTES,
+            },
+            keep_logs: raw.output.keep_logs.unwrap_or(DEFAULT_KEEP_LOGS),
+        };
+
+        let mut patterns = Vec::new();
+        for pattern in &raw.redaction.patterns {
+            let re = Regex::new(pattern).map_err(|e| ConfigError::InvalidRedactionPattern {
+                pattern: pattern.clone(),
+                reason: e.to_string(),
+            })?;
+            patterns.push(re);
+        }
+        let redaction = RedactionConfig {
+            enabled: raw.redaction.enabled.unwrap_or(true),
+            high_entropy: raw.redaction.high_entropy.unwrap_or(true),
+            patterns,
+        };
+
+        let mut locks = HashMap::new();
+        for (name, lock) in raw.locks {
+            if lock.capacity == 0 {
+                return Err(ConfigError::InvalidLockCapacity { name }.into());
+            }
+            locks.insert(name, lock.capacity);
+        }
+
+        let env_policy = EnvPolicy {
+            inherit: raw.inherit_env.unwrap_or(true),
+            passthrough: raw.env_passthrough,
+        };
+        let prompt = PromptConfig::default().refine(raw.prompt, "[prompt]")?;
+        let costs = Self::convert_costs(raw.costs)?;
+
         // Parse setup commands
         let mut setup = Vec::new();
         for (idx, raw_setup) in raw.setup.into_iter().enumerate() {
@@ -153,7 +899,9 @@ impl Config {
                 },
                 env: raw_setup.env,
                 timeout: raw_setup.timeout.map(Duration::from_secs),
+                kill_grace: raw_setup.kill_grace.map(Duration::from_secs),
                 cwd: raw_setup.cwd,
+                env_policy: env_policy.clone(),
             });
         }
 
@@ -208,6 +956,97 @@ impl Config {
 
             let enabled = raw_check.enabled.unwrap_or(true);
 
+            let mut retry_on = Vec::new();
+            for value in &raw_check.retry_on {
+                let condition = RetryCondition::parse(value).map_err(|reason| {
+                    ConfigError::InvalidRetryCondition {
+                        name: raw_check.name.clone(),
+                        value: value.clone(),
+                        reason,
+                    }
+                })?;
+                retry_on.push(condition);
+            }
+
+            let success_exit_codes = raw_check.success_exit_codes.unwrap_or_else(|| vec![0]);
+            if let Some(code) = raw_check
+                .error_exit_codes
+                .iter()
+                .find(|code| success_exit_codes.contains(code))
+            {
+                return Err(ConfigError::ConflictingExitCode {
+                    name: raw_check.name,
+                    code: *code,
+                }
+                .into());
+            }
+
+            let mut fail_if_output_matches = Vec::new();
+            for pattern in &raw_check.fail_if_output_matches {
+                let re = Regex::new(pattern).map_err(|e| ConfigError::InvalidOutputPattern {
+                    name: raw_check.name.clone(),
+                    pattern: pattern.clone(),
+                    reason: e.to_string(),
+                })?;
+                fail_if_output_matches.push(re);
+            }
+
+            let weight = raw_check.weight.unwrap_or(1);
+            if weight == 0 {
+                return Err(ConfigError::InvalidWeight {
+                    name: raw_check.name,
+                }
+                .into());
+            }
+
+            let memory_mb = raw_check
+                .memory
+                .as_ref()
+                .map(|memory| {
+                    memory.to_mb().map_err(|reason| ConfigError::InvalidMemory {
+                        owner: format!("check '{}'", raw_check.name),
+                        reason,
+                    })
+                })
+                .transpose()?;
+
+            let pty = match raw_check.pty {
+                None | Some(RawPty::Enabled(false)) => None,
+                Some(RawPty::Enabled(true)) => Some(PtySize::default()),
+                Some(RawPty::Size { columns, rows }) => {
+                    let default = PtySize::default();
+                    let size = PtySize {
+                        columns: columns.unwrap_or(default.columns),
+                        rows: rows.unwrap_or(default.rows),
+                    };
+                    if size.columns == 0 || size.rows == 0 {
+                        return Err(ConfigError::InvalidPtySize {
+                            name: raw_check.name,
+                        }
+                        .into());
+                    }
+                    Some(size)
+                }
+            };
+
+            let fix_scope = raw_check
+                .fix_scope
+                .map(|scope| {
+                    FixScope::parse(scope).map_err(|reason| ConfigError::InvalidFixScope {
+                        name: raw_check.name.clone(),
+                        reason,
+                    })
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            let check_prompt = match raw_check.prompt {
+                Some(raw_prompt) => {
+                    prompt.refine(raw_prompt, &format!("check '{}'", raw_check.name))?
+                }
+                None => prompt.clone(),
+            };
+
             checks.push(Check {
                 name: raw_check.name,
                 command,
@@ -215,33 +1054,63 @@ impl Config {
                 fixer,
                 env: raw_check.env,
                 timeout,
+                kill_grace: raw_check.kill_grace.map(Duration::from_secs),
                 enabled,
                 tags: raw_check.tags,
                 description: raw_check.description,
                 cwd: raw_check.cwd,
                 lock: raw_check.lock,
+                weight,
+                memory_mb,
+                watch: raw_check.watch,
+                retries: raw_check.retries,
+                retry_on,
+                critical: raw_check.critical,
+                pty,
+                success_exit_codes,
+                error_exit_codes: raw_check.error_exit_codes,
+                fail_if_output_matches,
+                mode: raw_check.mode,
+                env_policy: EnvPolicy {
+                    inherit: raw_check.inherit_env.unwrap_or(env_policy.inherit),
+                    passthrough: env_policy
+                        .passthrough
+                        .iter()
+                        .chain(&raw_check.env_passthrough)
+                        .cloned()
+                        .collect(),
+                },
+                fix_scope,
+                on_scope_violation: raw_check.on_scope_violation,
+                prompt: check_prompt,
             });
         }
 
         let agent = raw
             .agent
-            .map(|agent| Self::convert_agent("agent", agent))
+            .map(|agent| Self::convert_agent("agent", agent, &env_policy))
             .transpose()?;
 
         let agents = Agents {
             analyzer: raw
                 .agents
                 .analyzer
-                .map(|agent| Self::convert_agent("analyzer", agent))
+                .map(|agent| Self::convert_agent("analyzer", agent, &env_policy))
                 .transpose()?,
             fixer: raw
                 .agents
                 .fixer
-                .map(|agent| Self::convert_agent("fixer", agent))
+                .map(|agent| Self::convert_agent("fixer", agent, &env_policy))
                 .transpose()?,
         };
 
         Ok(Config {
+            env_policy,
+            pool,
+            output,
+            redaction,
+            costs,
+            locks,
             setup,
             checks,
             agent,
@@ -251,13 +1120,50 @@ impl Config {
 }
 
 impl Config {
-    fn convert_agent(role: &str, raw: RawAgent) -> Result<Agent> {
+    fn convert_costs(raw: RawCosts) -> Result<CostConfig> {
+        let negative = |value: f64| value.is_nan() || value < 0.0;
+        let invalid = |owner: String, value: f64| ConfigError::InvalidCost {
+            owner,
+            reason: format!("{value} is not a non-negative number"),
+        };
+        if let Some(max_cost) = raw.max_cost.filter(|cost| negative(*cost)) {
+            return Err(invalid("costs.max_cost".to_string(), max_cost).into());
+        }
+        let mut prices = HashMap::new();
+        for (model, raw_price) in raw.prices {
+            let price = Price {
+                input: raw_price.input,
+                cached_input: raw_price.cached_input.unwrap_or(raw_price.input),
+                output: raw_price.output,
+            };
+            let owner = format!("price of '{model}'");
+            for value in [price.input, price.cached_input, price.output] {
+                if negative(value) {
+                    return Err(invalid(owner, value).into());
+                }
+            }
+            prices.insert(model, price);
+        }
+        Ok(CostConfig {
+            max_cost: raw.max_cost,
+            max_tokens: raw.max_tokens,
+            prices,
+        })
+    }
+
+    fn convert_agent(role: &str, raw: RawAgent, global: &EnvPolicy) -> Result<Agent> {
         if raw.command.is_empty() {
             return Err(ConfigError::EmptyAgentCommand {
                 role: role.to_string(),
             }
             .into());
         }
+        if raw.max_concurrency == Some(0) {
+            return Err(ConfigError::InvalidAgentConcurrency {
+                role: role.to_string(),
+            }
+            .into());
+        }
         Ok(Agent {
             command: CommandSpec {
                 program: raw.command[0].clone(),
@@ -265,10 +1171,47 @@ impl Config {
             },
             env: raw.env,
             timeout: raw.timeout.map(Duration::from_secs),
+            kill_grace: raw.kill_grace.map(Duration::from_secs),
+            env_policy: agent_env_policy(
+                raw.clear_env.unwrap_or(true),
+                global,
+                &raw.env_passthrough,
+            ),
+            output: raw.output,
+            max_concurrency: raw.max_concurrency,
+            rate_limit_retries: raw.rate_limit_retries.unwrap_or(DEFAULT_RATE_LIMIT_RETRIES),
+            rate_limit_backoff: raw
+                .rate_limit_backoff
+                .map_or(DEFAULT_RATE_LIMIT_BACKOFF, Duration::from_secs),
         })
     }
 }
 
+/// Environment policy for an agent: cleared by default, keeping the agent baseline plus the
+/// global and the agent's own passthrough lists. With `clear_env = false` the agent follows
+/// the global `inherit_env` like a check does.
+pub fn agent_env_policy(clear: bool, global: &EnvPolicy, passthrough: &[String]) -> EnvPolicy {
+    if !clear {
+        return EnvPolicy {
+            inherit: global.inherit,
+            passthrough: global
+                .passthrough
+                .iter()
+                .chain(passthrough)
+                .cloned()
+                .collect(),
+        };
+    }
+    EnvPolicy::cleared(
+        AGENT_BASELINE_ENV
+            .iter()
+            .map(|name| name.to_string())
+            .chain(global.passthrough.iter().cloned())
+            .chain(passthrough.iter().cloned())
+            .collect(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -297,11 +1240,23 @@ command = ["cargo", "test", "--all"]
 formatter = ["format-output"]
 fixer = ["cargo", "fix"]
 timeout = 300
+kill_grace = 10
 enabled = false
 tags = ["rust", "unit"]
 description = "Run unit tests"
 cwd = "./backend"
 lock = "backend"
+cpus = 4
+memory = "2G"
+watch = ["src/**/*.rs", "Cargo.toml"]
+retries = 2
+retry_on = ["timeout", "exit:137", "output:connection (reset|refused)"]
+critical = true
+pty = { columns = 200 }
+success_exit_codes = [0, 1]
+error_exit_codes = [2, 101]
+fail_if_output_matches = ["^ERROR:"]
+mode = "advisory"
 
 [checks.env]
 RUST_BACKTRACE = "1"
@@ -315,11 +1270,34 @@ RUST_BACKTRACE = "1"
         assert_eq!(check.formatter.as_ref().unwrap().program, "format-output");
         assert_eq!(check.fixer.as_ref().unwrap().program, "cargo");
         assert_eq!(check.timeout, Some(Duration::from_secs(300)));
+        assert_eq!(check.kill_grace, Some(Duration::from_secs(10)));
         assert!(!check.enabled);
         assert_eq!(check.tags, vec!["rust", "unit"]);
         assert_eq!(check.description, Some("Run unit tests".to_string()));
         assert_eq!(check.cwd, Some("./backend".to_string()));
         assert_eq!(check.lock, Some("backend".to_string()));
+        assert_eq!(check.weight, 4);
+        assert_eq!(check.memory_mb, Some(2048));
+        assert_eq!(check.watch, vec!["src/**/*.rs", "Cargo.toml"]);
+        assert_eq!(check.retries, 2);
+        assert!(check.critical);
+        assert_eq!(check.success_exit_codes, vec![0, 1]);
+        assert_eq!(check.error_exit_codes, vec![2, 101]);
+        assert!(check.fail_if_output_matches[0].is_match("ERROR: boom"));
+        assert!(check.is_advisory());
+        assert_eq!(
+            check.pty,
+            Some(PtySize {
+                columns: 200,
+                rows: 40
+            })
+        );
+        assert_eq!(check.retry_on.len(), 3);
+        assert!(matches!(check.retry_on[0], RetryCondition::Timeout));
+        assert!(matches!(check.retry_on[1], RetryCondition::ExitCode(137)));
+        assert!(
+            matches!(&check.retry_on[2], RetryCondition::Output(re) if re.is_match("connection refused"))
+        );
         assert_eq!(check.env.get("RUST_BACKTRACE"), Some(&"1".to_string()));
     }
 
@@ -381,12 +1359,18 @@ command = ["cargo", "test"]
 [agent]
 command = ["codex", "exec", "--json"]
 timeout = 600
+max_concurrency = 2
+rate_limit_backoff = 5
 "#;
         let config = Config::from_toml(toml).unwrap();
 
         let agent = config.agent.as_ref().unwrap();
         assert_eq!(agent.command.program, "codex");
         assert_eq!(agent.timeout, Some(Duration::from_secs(600)));
+        assert_eq!(agent.output.resolve(&agent.command), AgentOutput::CodexJson);
+        assert_eq!(agent.max_concurrency, Some(2));
+        assert_eq!(agent.rate_limit_retries, DEFAULT_RATE_LIMIT_RETRIES);
+        assert_eq!(agent.rate_limit_backoff, Duration::from_secs(5));
         assert!(config.agents.analyzer.is_none());
         assert!(config.agents.fixer.is_none());
     }
@@ -434,6 +1418,20 @@ fixer = []
         assert!(result.unwrap_err().to_string().contains("fixer"));
     }
 
+    #[test]
+    fn exit_code_in_success_and_error_fails() {
+        let toml = r#"
+[[checks]]
+name = "bad"
+command = ["cargo", "test"]
+success_exit_codes = [0, 1]
+error_exit_codes = [1]
+"#;
+        let result = Config::from_toml(toml);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("exit code 1"));
+    }
+
     #[test]
     fn empty_agent_command_fails() {
         let toml = r#"
@@ -464,6 +1462,228 @@ command = []
         assert!(result.unwrap_err().to_string().contains("agent"));
     }
 
+    #[test]
+    fn invalid_retry_condition_fails() {
+        let toml = r#"
+[[checks]]
+name = "flaky"
+command = ["cargo", "test"]
+retries = 1
+retry_on = ["sometimes"]
+"#;
+        let result = Config::from_toml(toml);
+        assert!(result.is_err());
+        let msg = result.unwrap_err().to_string();
+        assert!(msg.contains("flaky"));
+        assert!(msg.contains("sometimes"));
+    }
+
+    #[test]
+    fn parse_fix_scope() {
+        let toml = r#"
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+fix_scope = "annotated"
+
+[[checks]]
+name = "docs"
+command = ["mdlint"]
+fix_scope = ["docs/**", "*.md"]
+on_scope_violation = "flag"
+
+[[checks]]
+name = "test"
+command = ["cargo", "test"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let annotated = [PathBuf::from("src/a.rs")];
+
+        let lint = &config.checks[0];
+        assert!(matches!(lint.fix_scope, FixScope::Annotated));
+        assert_eq!(lint.on_scope_violation, ScopeViolation::Revert);
+        assert!(lint.fix_scope.allows(Path::new("src/a.rs"), &annotated));
+        assert!(!lint.fix_scope.allows(Path::new("src/b.rs"), &annotated));
+
+        let docs = &config.checks[1];
+        assert_eq!(docs.on_scope_violation, ScopeViolation::Flag);
+        assert!(docs.fix_scope.allows(Path::new("docs/guide/intro.md"), &[]));
+        assert!(docs.fix_scope.allows(Path::new("README.md"), &[]));
+        assert!(!docs.fix_scope.allows(Path::new("src/a.rs"), &annotated));
+
+        let test = &config.checks[2];
+        assert!(test.fix_scope.allows(Path::new("Cargo.lock"), &annotated));
+
+        let toml = r#"
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+fix_scope = "nearby"
+"#;
+        let msg = Config::from_toml(toml).unwrap_err().to_string();
+        assert!(msg.contains("fix_scope"));
+        assert!(msg.contains("nearby"));
+    }
+
+    #[test]
+    fn parse_costs() {
+        let toml = r#"
+[costs]
+max_cost = 2.5
+max_tokens = 1000000
+
+[costs.prices."local-model"]
+input = 0.5
+output = 1.5
+
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.costs.max_cost, Some(2.5));
+        assert_eq!(config.costs.max_tokens, Some(1_000_000));
+        let price = config.costs.prices["local-model"];
+        assert_eq!(price.cached_input, price.input);
+        assert_eq!(price.output, 1.5);
+
+        let toml = r#"
+[costs.prices."local-model"]
+input = -1.0
+output = 1.0
+"#;
+        let msg = Config::from_toml(toml).unwrap_err().to_string();
+        assert!(msg.contains("local-model"), "{msg}");
+    }
+
+    #[test]
+    fn check_prompt_refines_global_prompt() {
+        let toml = r#"
+[prompt]
+template = "Fix {{check}}"
+context_lines = 3
+instructions = ["AGENTS.md"]
+
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+prompt = { template_file = "lint.md", instructions = ["docs/style.md"] }
+
+[[checks]]
+name = "test"
+command = ["cargo", "test"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+
+        let lint = &config.checks[0].prompt;
+        assert_eq!(lint.template, Some(PromptTemplate::File("lint.md".into())));
+        assert_eq!(lint.context_lines, 3);
+        assert_eq!(
+            lint.instructions,
+            [PathBuf::from("AGENTS.md"), PathBuf::from("docs/style.md")]
+        );
+
+        let test = &config.checks[1].prompt;
+        let inline = PromptTemplate::Inline("Fix {{check}}".to_string());
+        assert_eq!(test.template, Some(inline));
+        assert_eq!(test.instructions, [PathBuf::from("AGENTS.md")]);
+
+        let toml = r#"
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+prompt = { template = "Fix it", template_file = "lint.md" }
+"#;
+        let msg = Config::from_toml(toml).unwrap_err().to_string();
+        assert!(msg.contains("check 'lint'"));
+    }
+
+    #[test]
+    fn parse_pool_and_lock_capacity() {
+        let toml = r#"
+[pool]
+memory = 16384
+
+[locks.database]
+capacity = 2
+
+[locks.cache]
+
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+memory = "512M"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.pool.memory_mb, Some(16384));
+        assert_eq!(config.locks.get("database"), Some(&2));
+        assert_eq!(config.locks.get("cache"), Some(&1));
+        assert_eq!(config.checks[0].weight, 1);
+        assert_eq!(config.checks[0].memory_mb, Some(512));
+        assert_eq!(config.memory_budget_mb(), Some(16384));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn memory_hints_without_a_budget_use_the_machines_memory() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.memory_budget_mb(), None);
+
+        let toml = r#"
+[[checks]]
+name = "test"
+command = ["cargo", "test"]
+memory = "2G"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.memory_budget_mb().is_some_and(|mb| mb > 0));
+    }
+
+    #[test]
+    fn parse_output_limits() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.output.max_bytes, DEFAULT_MAX_OUTPUT_BYTES);
+        assert_eq!(config.output.keep_logs, DEFAULT_KEEP_LOGS);
+
+        let toml = r#"
+[output]
+max_bytes = "256K"
+keep_logs = 3
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.output.max_bytes, 256 * 1024);
+        assert_eq!(config.output.keep_logs, 3);
+
+        let config = Config::from_toml("[output]\nmax_bytes = 4096\n").unwrap();
+        assert_eq!(config.output.max_bytes, 4096);
+    }
+
+    #[test]
+    fn invalid_resource_hints_fail() {
+        let zero_weight = r#"
+[[checks]]
+name = "test"
+command = ["cargo", "test"]
+weight = 0
+"#;
+        assert!(Config::from_toml(zero_weight).is_err());
+
+        let bad_memory = r#"
+[[checks]]
+name = "test"
+command = ["cargo", "test"]
+memory = "lots"
+"#;
+        let err = Config::from_toml(bad_memory).unwrap_err();
+        assert!(err.to_string().contains("check 'test'"));
+
+        let zero_capacity = r#"
+[locks.database]
+capacity = 0
+"#;
+        assert!(Config::from_toml(zero_capacity).is_err());
+    }
+
     #[test]
     fn default_enabled_is_true() {
         let toml = r#"
@@ -535,4 +1755,105 @@ command = []
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("setup"));
     }
+
+    #[test]
+    fn env_policy_layers_global_check_and_agent_settings() {
+        let toml = r#"
+inherit_env = false
+env_passthrough = ["CARGO_*"]
+
+[[checks]]
+name = "clean"
+command = ["cargo", "test"]
+env_passthrough = ["DATABASE_URL"]
+
+[[checks]]
+name = "legacy"
+command = ["make"]
+inherit_env = true
+
+[agent]
+command = ["codex"]
+env_passthrough = ["GH_HOST"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        let clean = &config.checks[0].env_policy;
+        assert!(!clean.inherit);
+        assert_eq!(clean.passthrough, vec!["CARGO_*", "DATABASE_URL"]);
+        assert!(config.checks[1].env_policy.inherit);
+
+        let agent = &config.agent.as_ref().unwrap().env_policy;
+        assert!(!agent.inherit);
+        assert!(agent.allows("OPENAI_API_KEY"));
+        assert!(agent.allows("CARGO_HOME"));
+        assert!(agent.allows("GH_HOST"));
+        assert!(!agent.allows("AWS_SECRET_ACCESS_KEY"));
+    }
+
+    #[test]
+    fn agents_clear_env_by_default_but_checks_inherit() {
+        let toml = r#"
+[[checks]]
+name = "lint"
+command = ["cargo", "clippy"]
+
+[agent]
+command = ["claude"]
+
+[agents.fixer]
+command = ["codex"]
+clear_env = false
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.checks[0].env_policy.inherit);
+        assert!(!config.agent.unwrap().env_policy.inherit);
+        assert!(config.agents.fixer.unwrap().env_policy.inherit);
+
+        let strict = Config::from_toml(&format!("inherit_env = false\n{toml}")).unwrap();
+        let fixer = strict.agents.fixer.unwrap().env_policy;
+        assert!(!fixer.inherit);
+        assert!(!fixer.allows("OPENAI_API_KEY"));
+    }
+
+    #[test]
+    fn cleared_environment_keeps_baseline_passthrough_and_own_vars() {
+        let policy = EnvPolicy::cleared(vec!["CARGO_*".to_string(), "CI".to_string()]);
+        let parent = [
+            ("PATH", "/usr/bin"),
+            ("LC_ALL", "C"),
+            ("CARGO_HOME", "/cargo"),
+            ("CI", "1"),
+            ("CIRCLE_TOKEN", "secret"),
+            ("AWS_ACCESS_KEY_ID", "AKIA"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let vars = HashMap::from([("RUST_LOG".to_string(), "debug".to_string())]);
+
+        let env = policy.resolve_with(&vars, parent);
+        assert!(env.clear);
+        let mut names: Vec<_> = env.vars.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, ["CARGO_HOME", "CI", "LC_ALL", "PATH", "RUST_LOG"]);
+
+        let inherited = EnvPolicy::default().resolve_with(&vars, []);
+        assert!(!inherited.clear);
+        assert_eq!(inherited.vars, vars);
+    }
+
+    #[test]
+    fn parse_redaction_settings() {
+        let toml = r#"
+[redaction]
+high_entropy = false
+patterns = ['session=(?P<secret>\w+)']
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.redaction.enabled);
+        assert!(!config.redaction.high_entropy);
+        assert_eq!(config.redaction.patterns.len(), 1);
+
+        let invalid = "[redaction]\npatterns = ['(unclosed']\n";
+        let err = Config::from_toml(invalid).unwrap_err();
+        assert!(err.to_string().contains("invalid redaction pattern"));
+    }
 }
@@ -0,0 +1,383 @@
+//! Tokens and estimated cost of solver runs, the per-run budget, and the cost report kept
+//! under `<root>/.scanner/costs.jsonl`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::{CostConfig, Price};
+use crate::history::{STATE_DIR, ensure_state_dir};
+use crate::transcript::TokenUsage;
+
+/// Prices (USD per million tokens) of the models `--agent` picks by default and their
+/// siblings. `[costs.prices]` adds to and overrides these.
+const DEFAULT_PRICES: &[(&str, Price)] = &[
+    (
+        "gpt-5.1-codex",
+        Price {
+            input: 1.25,
+            cached_input: 0.125,
+            output: 10.0,
+        },
+    ),
+    (
+        "gpt-5-codex",
+        Price {
+            input: 1.25,
+            cached_input: 0.125,
+            output: 10.0,
+        },
+    ),
+    (
+        "claude-opus-4-5",
+        Price {
+            input: 5.0,
+            cached_input: 0.5,
+            output: 25.0,
+        },
+    ),
+    (
+        "claude-sonnet-4-5",
+        Price {
+            input: 3.0,
+            cached_input: 0.3,
+            output: 15.0,
+        },
+    ),
+    (
+        "claude-haiku-4-5",
+        Price {
+            input: 1.0,
+            cached_input: 0.1,
+            output: 5.0,
+        },
+    ),
+];
+
+/// Short model names agents accept (`claude --model sonnet`) and the models they stand for.
+const MODEL_ALIASES: &[(&str, &str)] = &[
+    ("opus", "claude-opus-4-5"),
+    ("sonnet", "claude-sonnet-4-5"),
+    ("haiku", "claude-haiku-4-5"),
+];
+
+/// What one solver run spent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SolverCost {
+    pub check: String,
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+    /// Estimated cost in USD; `None` when the model has no known price.
+    #[serde(rename = "cost_usd")]
+    pub cost: Option<f64>,
+}
+
+/// What all solver runs of a scanner run spent together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostTotals {
+    /// Agent runs recorded: each attempt counts, retries and analyzer runs included.
+    pub runs: usize,
+    pub usage: TokenUsage,
+    /// Estimated cost in USD of the runs whose model has a price.
+    pub cost: f64,
+    /// Runs that reported tokens for a model without a known price.
+    pub unpriced: usize,
+}
+
+impl SolverCost {
+    /// Add another attempt of the same run.
+    pub fn add(&mut self, other: &SolverCost) {
+        self.usage.add(other.usage);
+        self.cost = self.cost.zip(other.cost).map(|(a, b)| a + b);
+    }
+}
+
+impl fmt::Display for SolverCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} tokens", tokens(self.usage.total()))?;
+        match self.cost {
+            Some(cost) => write!(f, ", {}", dollars(cost)),
+            None => write!(f, ", cost unknown"),
+        }
+    }
+}
+
+impl fmt::Display for CostTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} agent run(s), {} tokens ({} in, {} out), {}",
+            self.runs,
+            tokens(self.usage.total()),
+            tokens(self.usage.input),
+            tokens(self.usage.output),
+            dollars(self.cost)
+        )?;
+        if self.unpriced > 0 {
+            write!(f, " ({} without a known price)", self.unpriced)?;
+        }
+        Ok(())
+    }
+}
+
+/// An estimate in USD, to the cent.
+fn dollars(cost: f64) -> String {
+    if cost > 0.0 && cost < 0.005 {
+        "<$0.01".to_string()
+    } else {
+        format!("~${cost:.2}")
+    }
+}
+
+/// `1234567` as `1.2M`, `45200` as `45.2k`.
+fn tokens(count: u64) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", count as f64 / 1e3),
+        _ => format!("{:.1}M", count as f64 / 1e6),
+    }
+}
+
+#[derive(Debug, Default)]
+struct LedgerData {
+    prices: HashMap<String, Price>,
+    max_cost: Option<f64>,
+    max_tokens: Option<u64>,
+    runs: Vec<SolverCost>,
+}
+
+/// Running account of solver spending, shared by the solvers of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger(Arc<Mutex<LedgerData>>);
+
+impl Ledger {
+    pub fn new(config: &CostConfig) -> Self {
+        Self(Arc::new(Mutex::new(LedgerData {
+            prices: config.prices.clone(),
+            max_cost: config.max_cost,
+            max_tokens: config.max_tokens,
+            runs: Vec::new(),
+        })))
+    }
+
+    fn data(&self) -> std::sync::MutexGuard<'_, LedgerData> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Account for a solver run of `check` on `model`.
+    pub fn record(&self, check: &str, model: Option<&str>, usage: TokenUsage) -> SolverCost {
+        let mut data = self.data();
+        let price = model.and_then(|model| price_for(&data.prices, model));
+        let run = SolverCost {
+            check: check.to_string(),
+            model: model.map(str::to_string),
+            usage,
+            cost: price.map(|price| estimate(price, usage)),
+        };
+        data.runs.push(run.clone());
+        run
+    }
+
+    pub fn runs(&self) -> Vec<SolverCost> {
+        self.data().runs.clone()
+    }
+
+    pub fn totals(&self) -> CostTotals {
+        let data = self.data();
+        let mut totals = CostTotals::default();
+        for run in &data.runs {
+            totals.runs += 1;
+            totals.usage.add(run.usage);
+            match run.cost {
+                Some(cost) => totals.cost += cost,
+                None if run.usage.total() > 0 => totals.unpriced += 1,
+                None => {}
+            }
+        }
+        totals
+    }
+
+    /// Why no more solvers may start, once `max_cost` or `max_tokens` is reached.
+    pub fn exhausted(&self) -> Option<String> {
+        let (max_cost, max_tokens) = {
+            let data = self.data();
+            (data.max_cost, data.max_tokens)
+        };
+        let totals = self.totals();
+        if let Some(max) = max_tokens
+            && totals.usage.total() >= max
+        {
+            return Some(format!(
+                "{} tokens spent, max_tokens is {}",
+                tokens(totals.usage.total()),
+                tokens(max)
+            ));
+        }
+        if let Some(max) = max_cost
+            && totals.cost >= max
+        {
+            return Some(format!("~${:.2} spent, max_cost is ${max:.2}", totals.cost));
+        }
+        None
+    }
+}
+
+/// The price for `model`, or for the model it is an alias of.
+fn price_for(configured: &HashMap<String, Price>, model: &str) -> Option<Price> {
+    lookup_price(configured, model).or_else(|| {
+        let (_, full) = MODEL_ALIASES.iter().find(|(alias, _)| *alias == model)?;
+        lookup_price(configured, full)
+    })
+}
+
+/// The configured price for `model`, else a built-in one. Keys also match models they are a
+/// prefix of (`claude-opus-4-5` prices `claude-opus-4-5-20251101`); the longest key wins.
+fn lookup_price(configured: &HashMap<String, Price>, model: &str) -> Option<Price> {
+    let longest = |prices: &mut dyn Iterator<Item = (&str, Price)>| {
+        prices
+            .filter(|(key, _)| model.starts_with(key))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    };
+    longest(&mut configured.iter().map(|(key, price)| (key.as_str(), *price)))
+        .or_else(|| longest(&mut DEFAULT_PRICES.iter().copied()))
+}
+
+fn estimate(price: Price, usage: TokenUsage) -> f64 {
+    let cached = usage.cached_input.min(usage.input);
+    let uncached = usage.input - cached;
+    (uncached as f64 * price.input
+        + cached as f64 * price.cached_input
+        + usage.output as f64 * price.output)
+        / 1e6
+}
+
+#[derive(Serialize)]
+struct ReportLine<'a> {
+    /// Seconds since the Unix epoch.
+    at: u64,
+    #[serde(flatten)]
+    run: &'a SolverCost,
+}
+
+/// Append `runs` to `<root>/.scanner/costs.jsonl`, one JSON object per line.
+pub fn append_report(root: &Path, runs: &[SolverCost]) -> Result<()> {
+    if runs.is_empty() {
+        return Ok(());
+    }
+    let dir = root.join(STATE_DIR);
+    ensure_state_dir(&dir)?;
+    let path = dir.join("costs.jsonl");
+    let at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut lines = String::new();
+    for run in runs {
+        lines.push_str(&serde_json::to_string(&ReportLine { at, run })?);
+        lines.push('\n');
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u64, cached_input: u64, output: u64) -> TokenUsage {
+        TokenUsage {
+            input,
+            cached_input,
+            output,
+        }
+    }
+
+    #[test]
+    fn runs_are_priced_by_longest_matching_model() {
+        let mut config = CostConfig::default();
+        config.prices.insert(
+            "claude-opus".to_string(),
+            Price {
+                input: 1.0,
+                cached_input: 1.0,
+                output: 1.0,
+            },
+        );
+        let ledger = Ledger::new(&config);
+
+        // The built-in claude-opus-4-5 is longer than the configured claude-opus, but
+        // configured prices come first.
+        let opus = ledger.record(
+            "lint",
+            Some("claude-opus-4-5-20251101"),
+            usage(1_000_000, 0, 1_000_000),
+        );
+        assert_eq!(opus.cost, Some(2.0));
+
+        let codex = ledger.record(
+            "test",
+            Some("gpt-5.1-codex-max"),
+            usage(2_000_000, 1_000_000, 100_000),
+        );
+        assert_eq!(codex.cost, Some(1.25 + 0.125 + 1.0));
+        assert_eq!(codex.to_string(), "2.1M tokens, ~$2.38");
+
+        let unknown = ledger.record("docs", Some("local-llm"), usage(500, 0, 20));
+        assert_eq!(unknown.cost, None);
+        assert_eq!(unknown.to_string(), "520 tokens, cost unknown");
+
+        // `--model sonnet` is priced as the model it stands for.
+        let alias = Ledger::new(&CostConfig::default()).record(
+            "lint",
+            Some("sonnet"),
+            usage(1_000_000, 0, 0),
+        );
+        assert_eq!(alias.cost, Some(3.0));
+
+        let totals = ledger.totals();
+        assert_eq!(totals.runs, 3);
+        assert_eq!(totals.usage.total(), 4_100_520);
+        assert_eq!(totals.unpriced, 1);
+        assert_eq!(
+            totals.to_string(),
+            "3 agent run(s), 4.1M tokens (3.0M in, 1.1M out), ~$4.38 (1 without a known price)"
+        );
+    }
+
+    #[test]
+    fn budget_is_exhausted_at_either_limit() {
+        let ledger = Ledger::new(&CostConfig {
+            max_cost: Some(1.0),
+            max_tokens: Some(1_000_000),
+            prices: HashMap::new(),
+        });
+        assert_eq!(ledger.exhausted(), None);
+
+        ledger.record("lint", Some("claude-opus-4-5"), usage(100_000, 0, 10_000));
+        assert_eq!(ledger.exhausted(), None);
+
+        ledger.record("lint", Some("claude-opus-4-5"), usage(100_000, 0, 40_000));
+        let reason = ledger.exhausted().expect("cost limit reached");
+        assert!(reason.contains("max_cost"), "{reason}");
+
+        let ledger = Ledger::new(&CostConfig {
+            max_tokens: Some(1_000),
+            ..CostConfig::default()
+        });
+        ledger.record("lint", None, usage(900, 0, 100));
+        let reason = ledger.exhausted().expect("token limit reached");
+        assert!(reason.contains("max_tokens"), "{reason}");
+    }
+}
@@ -34,6 +34,7 @@ pub async fn run_demo(use_tui: bool, use_color: bool) -> Result<()> {
                     .send(ui::UiEvent::CheckStarted {
                         name: name.clone(),
                         desc: Some(desc.clone()),
+                        expected: Some(Duration::from_millis(sleep_ms)),
                     })
                     .await;
             }
@@ -49,6 +50,9 @@ pub async fn run_demo(use_tui: bool, use_color: bool) -> Result<()> {
                             "ok".to_string()
                         },
                         output: Some(format!("log output for {name} (simulated)")),
+                        flaky: false,
+                        advisory: false,
+                        log_path: None,
                     })
                     .await;
             }
@@ -48,6 +48,68 @@ pub enum ConfigError {
     #[error("{role} agent must define a non-empty command")]
     EmptyAgentCommand { role: String },
 
+    /// A check's `retry_on` entry could not be parsed.
+    #[error("invalid retry_on '{value}' for check '{name}': {reason}")]
+    InvalidRetryCondition {
+        name: String,
+        value: String,
+        reason: String,
+    },
+
+    /// An exit code is listed as both a success and an error.
+    #[error(
+        "check '{name}' lists exit code {code} in both success_exit_codes and error_exit_codes"
+    )]
+    ConflictingExitCode { name: String, code: i32 },
+
+    /// A `fail_if_output_matches` entry is not a valid regex.
+    #[error("invalid fail_if_output_matches '{pattern}' for check '{name}': {reason}")]
+    InvalidOutputPattern {
+        name: String,
+        pattern: String,
+        reason: String,
+    },
+
+    /// A `[redaction] patterns` entry is not a valid regex.
+    #[error("invalid redaction pattern '{pattern}': {reason}")]
+    InvalidRedactionPattern { pattern: String, reason: String },
+
+    /// A check's `fix_scope` is neither a known keyword nor a list of valid globs.
+    #[error("invalid fix_scope for check '{name}': {reason}")]
+    InvalidFixScope { name: String, reason: String },
+
+    /// A cost limit or model price is negative (or not a number).
+    #[error("invalid {owner}: {reason}")]
+    InvalidCost { owner: String, reason: String },
+
+    /// A prompt sets both an inline template and a template file.
+    #[error("{owner} sets both template and template_file; use one")]
+    ConflictingPromptTemplate { owner: String },
+
+    /// A check declares a zero weight.
+    #[error("check '{name}' must have a weight of at least 1")]
+    InvalidWeight { name: String },
+
+    /// A check's pseudo-terminal has a zero dimension.
+    #[error("check '{name}' must have a pty of at least 1x1")]
+    InvalidPtySize { name: String },
+
+    /// A memory size could not be parsed.
+    #[error("invalid memory for {owner}: {reason}")]
+    InvalidMemory { owner: String, reason: String },
+
+    /// A size setting could not be parsed.
+    #[error("invalid {setting}: {reason}")]
+    InvalidSize { setting: String, reason: String },
+
+    /// A lock group declares a zero capacity.
+    #[error("lock '{name}' must have a capacity of at least 1")]
+    InvalidLockCapacity { name: String },
+
+    /// An agent allows zero concurrent runs.
+    #[error("{role} must have a max_concurrency of at least 1")]
+    InvalidAgentConcurrency { role: String },
+
     /// A setup step has an empty command.
     #[error("setup '{name}' must define a non-empty command")]
     EmptySetupCommand { name: String },
@@ -93,8 +155,21 @@ pub enum CliError {
     ChecksFailed { count: usize, reason: String },
 
     /// Checks still failing after fixes were applied.
-    #[error("{count} check(s) still failing after fixes")]
-    FixesIncomplete { count: usize },
+    #[error("{count} check(s) still failing after {rounds} fix round(s)")]
+    FixesIncomplete { count: usize, rounds: usize },
+
+    /// `--review` couldn't ask, so the proposed fixes were written out as patches.
+    #[error("{count} proposed fix(es) written to {} for review", dir.display())]
+    FixesProposed { count: usize, dir: PathBuf },
+
+    /// A fix round left the same errors behind as the one before, so further rounds were
+    /// not attempted.
+    #[error("{count} check(s) still failing; fix round {round} made no progress")]
+    FixesStalled { count: usize, round: usize },
+
+    /// Checks still failing after the solver budget (`max_cost`/`max_tokens`) ran out.
+    #[error("{count} check(s) still failing; solver budget exhausted ({reason})")]
+    BudgetExhausted { count: usize, reason: String },
 
     /// Checks still failing after fixes, but some failures are not auto-fixable
     /// because they produced no actionable GitHub Actions annotations.
@@ -102,6 +177,44 @@ pub enum CliError {
         "{count} check(s) still failing after fixes ({unfixable} not auto-fixable: no actionable GitHub Actions annotations)"
     )]
     FixesIncompleteUnfixable { count: usize, unfixable: usize },
+
+    /// `watch` reruns checks on the live working copy, so it has no snapshot to offer.
+    #[error("--snapshot is not supported by `watch`")]
+    SnapshotInWatch,
+
+    /// The run was cancelled (Ctrl+C, SIGTERM or quitting the TUI).
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl CliError {
+    /// Process exit code for this error (128 + SIGINT for cancellation, 1 otherwise).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Cancelled => 130,
+            _ => 1,
+        }
+    }
+}
+
+/// Errors from `--snapshot` worktrees.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// The root is not inside a git repository.
+    #[error("--snapshot and --review need a git repository, but {0} is not inside one")]
+    NotARepository(PathBuf),
+
+    /// The repository has no commit to base the worktree on.
+    #[error("--snapshot and --review need at least one commit in {0}")]
+    NoCommits(PathBuf),
+
+    /// A git command failed.
+    #[error("git {command} failed: {reason}")]
+    Git { command: String, reason: String },
+
+    /// The snapshot's changes don't apply to the working copy any more.
+    #[error("failed to apply snapshot changes: {0}")]
+    ApplyFailed(String),
 }
 
 /// Errors that can occur during process execution.
@@ -122,6 +235,10 @@ pub enum ProcessError {
     /// Failed to read from stdout/stderr.
     #[error("failed to read process output: {0}")]
     OutputReadFailed(String),
+
+    /// The process was stopped because the run was cancelled.
+    #[error("process cancelled")]
+    Cancelled,
 }
 
 #[cfg(test)]
@@ -158,6 +275,19 @@ mod tests {
         assert!(err.to_string().contains("bar"));
     }
 
+    #[test]
+    fn cancelled_has_distinct_exit_code() {
+        assert_eq!(CliError::Cancelled.exit_code(), 130);
+        assert_eq!(
+            CliError::FixesIncomplete {
+                count: 1,
+                rounds: 1
+            }
+            .exit_code(),
+            1
+        );
+    }
+
     #[test]
     fn process_error_display() {
         let err = ProcessError::SpawnFailed("not found".to_string());
@@ -0,0 +1,163 @@
+//! `scanner explain`: show what each setup step, check and agent would run with, so the
+//! environment policy can be reviewed without running anything.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::config::{self, Agent, Check, CommandSpec, Config, Environment};
+use crate::redact::Redactor;
+
+/// Name fragments of variables whose values are never printed.
+const SECRET_MARKERS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "KEY",
+    "PASSWORD",
+    "PASSWD",
+    "PASS",
+    "CREDENTIAL",
+    "AUTH",
+    "SESSION",
+    "PRIVATE",
+    "COOKIE",
+];
+
+/// Describe the commands and effective environments for `checks`, the solver agent and the
+/// analyzer that plans its fixes, if any.
+pub fn explain(
+    cfg: &Config,
+    checks: &[Check],
+    agent: Option<&Agent>,
+    analyzer: Option<&Agent>,
+) -> String {
+    let redactor = Redactor::new(&cfg.redaction);
+    let mut out = String::new();
+    for setup in &cfg.setup {
+        section(
+            &mut out,
+            &format!("setup {}", setup.name),
+            &setup.command,
+            &setup.env_policy.resolve(&setup.env),
+            &redactor,
+        );
+    }
+    for check in checks {
+        section(
+            &mut out,
+            &format!("check {}", check.name),
+            &check.command,
+            &check.environment(),
+            &redactor,
+        );
+    }
+    match agent {
+        Some(agent) => section(
+            &mut out,
+            "agent",
+            &agent.command,
+            &agent.env_policy.resolve(&agent.env),
+            &redactor,
+        ),
+        None => out.push_str("agent: not configured\n"),
+    }
+    if let Some(analyzer) = analyzer {
+        section(
+            &mut out,
+            "analyzer",
+            &analyzer.command,
+            &analyzer.env_policy.resolve(&analyzer.env),
+            &redactor,
+        );
+    }
+    out
+}
+
+fn section(
+    out: &mut String,
+    title: &str,
+    command: &CommandSpec,
+    env: &Environment,
+    redactor: &Redactor,
+) {
+    let _ = writeln!(out, "{title}: {}", command_line(command));
+    let effective: BTreeMap<String, String> = if env.clear {
+        env.vars.clone().into_iter().collect()
+    } else {
+        config::scanner_env().chain(env.vars.clone()).collect()
+    };
+    let origin = if env.clear {
+        "cleared, allowlisted variables only"
+    } else {
+        "inherited from scanner"
+    };
+    let _ = writeln!(
+        out,
+        "  environment ({origin}, {} variables):",
+        effective.len()
+    );
+    for (name, value) in &effective {
+        let _ = writeln!(out, "    {name}={}", display_value(name, value, redactor));
+    }
+    out.push('\n');
+}
+
+fn command_line(command: &CommandSpec) -> String {
+    std::iter::once(command.program.as_str())
+        .chain(command.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a variable's name suggests its value is a credential.
+pub fn looks_secret(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| upper.contains(marker))
+}
+
+/// A variable's value, masked entirely when the name suggests a credential and otherwise
+/// with any secret inside it (e.g. a password in a connection string) redacted.
+fn display_value(name: &str, value: &str, redactor: &Redactor) -> String {
+    if looks_secret(name) && !value.is_empty() {
+        format!("<masked, {} chars>", value.chars().count())
+    } else {
+        redactor.redact(value).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn secret_values_are_masked() {
+        let env = Environment {
+            clear: true,
+            vars: HashMap::from([
+                ("GITHUB_TOKEN".to_string(), "ghp_abcdef".to_string()),
+                ("aws_secret_access_key".to_string(), "xyz".to_string()),
+                ("RUST_LOG".to_string(), "debug".to_string()),
+                (
+                    "DATABASE_URL".to_string(),
+                    "postgres://app:hunter2@db/app".to_string(),
+                ),
+            ]),
+        };
+        let command = CommandSpec {
+            program: "cargo".to_string(),
+            args: vec!["test".to_string()],
+        };
+        let mut out = String::new();
+        let redactor = Redactor::new(&Default::default());
+        section(&mut out, "check test", &command, &env, &redactor);
+
+        assert!(out.starts_with("check test: cargo test\n"));
+        assert!(out.contains("cleared, allowlisted variables only, 4 variables"));
+        assert!(out.contains("GITHUB_TOKEN=<masked, 10 chars>"));
+        assert!(out.contains("aws_secret_access_key=<masked, 3 chars>"));
+        assert!(out.contains("RUST_LOG=debug"));
+        assert!(!out.contains("ghp_abcdef"));
+        assert!(out.contains("DATABASE_URL=postgres://app:"));
+        assert!(!out.contains("hunter2"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Local, per-project run history kept under `<root>/.scanner/`.
+//!
+//! The history is best-effort: a missing or corrupt file simply starts a fresh history.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::runner::CheckResult;
+
+/// Directory (relative to the root) holding scanner's local state.
+pub const STATE_DIR: &str = ".scanner";
+
+/// Number of recent runs considered when deciding whether a check is flaky.
+const FLAKY_WINDOW: usize = 20;
+
+/// Flaky runs within the window after which a check counts as repeatedly flaky.
+const FLAKY_THRESHOLD: usize = 2;
+
+/// Number of recent durations kept per check.
+const DURATION_WINDOW: usize = 20;
+
+/// Samples needed before a slow run is reported as a regression.
+const REGRESSION_MIN_SAMPLES: usize = 3;
+
+/// A run slower than this multiple of the median counts as a regression...
+const REGRESSION_FACTOR: f64 = 2.0;
+
+/// ...as long as it is also at least this much slower (ignores noise on fast checks).
+const REGRESSION_MIN_DELTA: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryData {
+    #[serde(default)]
+    checks: BTreeMap<String, CheckHistory>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckHistory {
+    /// Recent runs, newest last: `true` when the run only passed after a retry.
+    #[serde(default)]
+    flaky: VecDeque<bool>,
+    /// Recent run durations in seconds, newest last.
+    #[serde(default)]
+    durations: VecDeque<f64>,
+}
+
+impl CheckHistory {
+    fn median_duration(&self) -> Option<Duration> {
+        let mut samples: Vec<f64> = self.durations.iter().copied().collect();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_by(f64::total_cmp);
+        let mid = samples.len() / 2;
+        let median = if samples.len().is_multiple_of(2) {
+            (samples[mid - 1] + samples[mid]) / 2.0
+        } else {
+            samples[mid]
+        };
+        Some(Duration::from_secs_f64(median))
+    }
+}
+
+/// Create the state directory (`<root>/.scanner`) if needed.
+pub fn ensure_state_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    // Keep scanner's state out of `git status` without touching the project's .gitignore.
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        let _ = std::fs::write(&gitignore, "*\n");
+    }
+    Ok(())
+}
+
+/// Persistent history of check runs for one project.
+#[derive(Debug)]
+pub struct HistoryStore {
+    path: PathBuf,
+    data: HistoryData,
+}
+
+impl HistoryStore {
+    /// Load the history for `root`, starting empty if there is none (or it can't be read).
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(STATE_DIR).join("history.json");
+        let data = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Self { path, data }
+    }
+
+    /// Write the history back to disk.
+    pub fn save(&self) -> Result<()> {
+        let dir = self.path.parent().context("history path has no parent")?;
+        ensure_state_dir(dir)?;
+        let json = serde_json::to_string_pretty(&self.data)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    /// Record each (non-cancelled) check's duration and whether it passed only after a retry.
+    pub fn record(&mut self, results: &[CheckResult]) {
+        for result in results.iter().filter(|r| !r.cancelled) {
+            let entry = self
+                .data
+                .checks
+                .entry(result.check.name.clone())
+                .or_default();
+            if entry.flaky.len() >= FLAKY_WINDOW {
+                entry.flaky.pop_front();
+            }
+            entry.flaky.push_back(result.flaky);
+            if entry.durations.len() >= DURATION_WINDOW {
+                entry.durations.pop_front();
+            }
+            entry.durations.push_back(result.duration.as_secs_f64());
+        }
+    }
+
+    /// Median of the check's recent durations, if it has run before.
+    pub fn expected_duration(&self, check: &str) -> Option<Duration> {
+        self.data.checks.get(check)?.median_duration()
+    }
+
+    /// If `duration` is a significant slowdown against the check's history, the median it
+    /// regressed from. Call before recording the run in question.
+    pub fn regression(&self, check: &str, duration: Duration) -> Option<Duration> {
+        let history = self.data.checks.get(check)?;
+        if history.durations.len() < REGRESSION_MIN_SAMPLES {
+            return None;
+        }
+        let median = history.median_duration()?;
+        let regressed = duration.as_secs_f64() > median.as_secs_f64() * REGRESSION_FACTOR
+            && duration.saturating_sub(median) >= REGRESSION_MIN_DELTA;
+        regressed.then_some(median)
+    }
+
+    /// Number of flaky runs in the recent window.
+    pub fn flaky_count(&self, check: &str) -> usize {
+        self.data
+            .checks
+            .get(check)
+            .map(|h| h.flaky.iter().filter(|f| **f).count())
+            .unwrap_or(0)
+    }
+
+    /// Whether the check has been flaky often enough that its failures shouldn't be trusted.
+    pub fn is_repeatedly_flaky(&self, check: &str) -> bool {
+        self.flaky_count(check) >= FLAKY_THRESHOLD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Check, CommandSpec};
+    use std::collections::HashMap;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_root(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path =
+            std::env::temp_dir().join(format!("scanner-rs-{name}-{}-{nanos}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn result(name: &str, flaky: bool) -> CheckResult {
+        timed_result(name, flaky, Duration::from_secs(1))
+    }
+
+    fn timed_result(name: &str, flaky: bool, duration: Duration) -> CheckResult {
+        CheckResult {
+            check: Check {
+                name: name.to_string(),
+                command: CommandSpec {
+                    program: "echo".to_string(),
+                    args: vec![],
+                },
+                formatter: None,
+                fixer: None,
+                env: HashMap::new(),
+                timeout: None,
+                kill_grace: None,
+                enabled: true,
+                tags: vec![],
+                description: None,
+                cwd: None,
+                lock: None,
+                weight: 1,
+                memory_mb: None,
+                watch: vec![],
+                retries: 1,
+                retry_on: vec![],
+                critical: false,
+                pty: None,
+                success_exit_codes: vec![0],
+                error_exit_codes: vec![],
+                fail_if_output_matches: vec![],
+                mode: Default::default(),
+                env_policy: Default::default(),
+                fix_scope: Default::default(),
+                on_scope_violation: Default::default(),
+                prompt: Default::default(),
+            },
+            exit_code: Some(0),
+            raw_output: String::new(),
+            annotations: vec![],
+            cancelled: false,
+            attempts: if flaky { 2 } else { 1 },
+            flaky,
+            duration,
+            log_path: None,
+        }
+    }
+
+    #[test]
+    fn repeated_flakiness_is_detected() {
+        let root = temp_root("history-flaky");
+        let mut store = HistoryStore::load(&root);
+
+        store.record(&[result("it", true), result("unit", false)]);
+        assert!(!store.is_repeatedly_flaky("it"));
+
+        store.record(&[result("it", true), result("unit", false)]);
+        assert!(store.is_repeatedly_flaky("it"));
+        assert!(!store.is_repeatedly_flaky("unit"));
+        assert!(!store.is_repeatedly_flaky("unknown"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn expected_duration_is_the_median() {
+        let root = temp_root("history-median");
+        let mut store = HistoryStore::load(&root);
+        assert_eq!(store.expected_duration("test"), None);
+
+        for secs in [10, 300, 20] {
+            store.record(&[timed_result("test", false, Duration::from_secs(secs))]);
+        }
+        assert_eq!(
+            store.expected_duration("test"),
+            Some(Duration::from_secs(20))
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn significant_slowdowns_are_regressions() {
+        let root = temp_root("history-regression");
+        let mut store = HistoryStore::load(&root);
+        store.record(&[timed_result("test", false, Duration::from_secs(60))]);
+        // Too little history to judge.
+        assert_eq!(store.regression("test", Duration::from_secs(600)), None);
+
+        for _ in 0..2 {
+            store.record(&[timed_result("test", false, Duration::from_secs(60))]);
+        }
+        assert_eq!(
+            store.regression("test", Duration::from_secs(150)),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(store.regression("test", Duration::from_secs(90)), None);
+
+        // Fast checks need an absolute slowdown too, not just a ratio.
+        for _ in 0..3 {
+            store.record(&[timed_result("lint", false, Duration::from_millis(500))]);
+        }
+        assert_eq!(store.regression("lint", Duration::from_secs(2)), None);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn history_round_trips_through_disk() {
+        let root = temp_root("history-save");
+        let mut store = HistoryStore::load(&root);
+        store.record(&[result("it", true), result("it", true)]);
+        store.save().unwrap();
+
+        let reloaded = HistoryStore::load(&root);
+        assert_eq!(reloaded.flaky_count("it"), 2);
+        assert!(root.join(STATE_DIR).join(".gitignore").exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn corrupt_history_starts_fresh() {
+        let root = temp_root("history-corrupt");
+        std::fs::create_dir_all(root.join(STATE_DIR)).unwrap();
+        std::fs::write(root.join(STATE_DIR).join("history.json"), "{not json").unwrap();
+
+        let store = HistoryStore::load(&root);
+        assert_eq!(store.flaky_count("it"), 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
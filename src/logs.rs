@@ -0,0 +1,103 @@
+//! Full check output, one directory per run under `<root>/.scanner/logs/`.
+//!
+//! Checks only keep the head and tail of huge outputs in memory; the complete output is
+//! written here so it can still be inspected.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::history::{STATE_DIR, ensure_state_dir};
+
+/// The log directory of one run.
+#[derive(Debug, Clone)]
+pub struct RunLogs {
+    dir: PathBuf,
+}
+
+impl RunLogs {
+    /// Create a fresh log directory for this run, keeping only the newest `keep` runs.
+    ///
+    /// Returns `None` when logs are disabled (`keep == 0`) or the directory can't be created;
+    /// checks then just run without a full log.
+    pub fn create(root: &Path, keep: usize) -> Option<Self> {
+        if keep == 0 {
+            return None;
+        }
+        let state_dir = root.join(STATE_DIR);
+        ensure_state_dir(&state_dir).ok()?;
+        let logs_dir = state_dir.join("logs");
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir = logs_dir.join(format!("{nanos:020}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).ok()?;
+
+        prune(&logs_dir, keep);
+        Some(Self { dir })
+    }
+
+    /// Log file for a check (or other named task) within this run.
+    pub fn path_for(&self, name: &str) -> PathBuf {
+        let file: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{file}.log"))
+    }
+}
+
+/// Remove all but the newest `keep` run directories (names sort chronologically).
+fn prune(logs_dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(logs_dir) else {
+        return;
+    };
+    let mut runs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    runs.sort();
+    let excess = runs.len().saturating_sub(keep);
+    for old in runs.into_iter().take(excess) {
+        let _ = std::fs::remove_dir_all(old);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_runs_are_pruned_and_names_sanitized() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("scanner-rs-logs-{}-{nanos}", std::process::id()));
+
+        let mut dirs = Vec::new();
+        for _ in 0..3 {
+            let logs = RunLogs::create(&root, 2).unwrap();
+            dirs.push(logs.dir.clone());
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        assert!(!dirs[0].exists());
+        assert!(dirs[1].exists() && dirs[2].exists());
+
+        let logs = RunLogs::create(&root, 2).unwrap();
+        let path = logs.path_for("solve:web/lint");
+        assert_eq!(path.file_name().unwrap(), "solve_web_lint.log");
+
+        assert!(RunLogs::create(&root, 0).is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
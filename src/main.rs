@@ -1,18 +1,37 @@
 mod agents;
+mod checkpoint;
 mod cli;
 mod config;
+mod cost;
 mod demo;
 mod error;
+mod explain;
 mod fix;
 mod gha;
+mod history;
+mod logs;
+mod plan;
 mod pool;
 mod process;
+mod prompt;
+#[cfg(unix)]
+mod pty;
+mod ratelimit;
+mod redact;
+mod review;
 mod runner;
+mod snapshot;
+mod transcript;
 mod ui;
+mod watch;
+
+use std::process::ExitCode;
 
 use anyhow::Result;
 use clap::Parser;
 
+use crate::error::CliError;
+
 #[derive(Parser, Debug, Clone)]
 #[command(
     name = "scanner",
@@ -57,6 +76,35 @@ pub struct Cli {
     #[arg(long)]
     force: bool,
 
+    /// Stop outstanding checks as soon as any check fails
+    #[arg(long, conflicts_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Run every check even when a `critical` check fails
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Also send failures of advisory checks to solvers
+    #[arg(long)]
+    fix_advisory: bool,
+
+    /// Repeat fixing and rerunning the failing checks up to this many times
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    fix_rounds: u32,
+
+    /// Run checks and solvers in a temporary git worktree and offer the resulting changes
+    #[arg(long)]
+    snapshot: bool,
+
+    /// Let solvers work on scratch worktrees and review their changes hunk by hunk
+    #[arg(long)]
+    review: bool,
+
+    /// Write proposed fixes as .patch files to this directory instead of applying them
+    /// (implies --review)
+    #[arg(long)]
+    patch_dir: Option<std::path::PathBuf>,
+
     /// Model name for the selected agent (e.g. gpt-5.1-codex-max, gpt-5-codex, sonnet, opus)
     #[arg(short = 'm', long)]
     model: Option<String>,
@@ -66,9 +114,18 @@ pub struct Cli {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
-    cli::run(cli).await
+    match cli::run(cli).await {
+        // Cancellation gets its own exit code so wrappers can tell it apart from failures.
+        // Returning (rather than exiting) lets the runtime drop, and with it any work still
+        // running, so its processes get killed.
+        Err(err) if matches!(err.downcast_ref::<CliError>(), Some(CliError::Cancelled)) => {
+            eprintln!("Error: {err}");
+            Ok(ExitCode::from(CliError::Cancelled.exit_code() as u8))
+        }
+        result => result.map(|()| ExitCode::SUCCESS),
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,110 @@
+//! The analyzer stage of a two-stage solve: what the analyzer agent is asked for, and the
+//! per-group plans read back from its answer for the fixer to follow.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// The task given to the analyzer agent in place of the solver's.
+pub const ANALYZER_TASK: &str = "\
+Analyze the following build/lint errors without changing any files. For each group, work \
+out the root cause, the files a fix has to touch, and whether the errors can be fixed \
+automatically by editing code (not, say, by installing tools, changing credentials or \
+making a product decision). Answer with a single JSON object and nothing else, shaped as \
+{\"groups\": [{\"error_type\": <the group's error_type>, \"fixable\": true or false, \
+\"root_cause\": <one or two sentences>, \"files\": [<paths to edit>], \
+\"steps\": [<what to change, in order>]}]}.";
+
+/// What the analyzer made of one error group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub error_type: String,
+    /// Whether a solver should try; groups judged unfixable are skipped.
+    #[serde(default = "fixable_by_default")]
+    pub fixable: bool,
+    #[serde(default)]
+    pub root_cause: String,
+    /// Files the fix is expected to touch.
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub steps: Vec<String>,
+}
+
+fn fixable_by_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    groups: Vec<Plan>,
+}
+
+/// Read the plans out of the analyzer's final message, keyed by error type. The JSON may
+/// be wrapped in prose or a code fence.
+pub fn parse_plans(text: &str) -> Result<HashMap<String, Plan>> {
+    let start = text.find('{');
+    let end = text.rfind('}');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err(anyhow!("the analyzer's answer holds no JSON object")),
+    };
+    let answer: Answer =
+        serde_json::from_str(json).context("failed to parse the analyzer's answer")?;
+    Ok(answer
+        .groups
+        .into_iter()
+        .map(|plan| (plan.error_type.clone(), plan))
+        .collect())
+}
+
+/// `plans` as text, for the `{{plan}}` placeholder.
+pub fn describe(plans: &HashMap<String, Plan>) -> String {
+    let mut sorted: Vec<&Plan> = plans.values().collect();
+    sorted.sort_by(|a, b| a.error_type.cmp(&b.error_type));
+    let mut out = String::new();
+    for plan in sorted {
+        let _ = writeln!(out, "{}: {}", plan.error_type, plan.root_cause);
+        if !plan.files.is_empty() {
+            let _ = writeln!(out, "  files: {}", plan.files.join(", "));
+        }
+        for (idx, step) in plan.steps.iter().enumerate() {
+            let _ = writeln!(out, "  {}. {step}", idx + 1);
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_are_read_from_a_fenced_answer() {
+        let answer = r#"Here is the analysis:
+```json
+{"groups": [
+  {"error_type": "E0308", "fixable": true, "root_cause": "wrong return type",
+   "files": ["src/a.rs"], "steps": ["return a String"]},
+  {"error_type": "missing-tool", "fixable": false, "root_cause": "protoc is not installed"},
+  {"error_type": "W1", "root_cause": "unused import"}
+]}
+```"#;
+        let plans = parse_plans(answer).unwrap();
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans["E0308"].files, vec!["src/a.rs"]);
+        assert!(!plans["missing-tool"].fixable);
+        assert!(plans["W1"].fixable);
+
+        assert_eq!(
+            describe(&plans),
+            "E0308: wrong return type\n  files: src/a.rs\n  1. return a String\n\
+             W1: unused import\n\
+             missing-tool: protoc is not installed"
+        );
+
+        assert!(parse_plans("I could not work it out.").is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use std::future::Future;
-use std::sync::Arc;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 
-use tokio::sync::Semaphore;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio_util::sync::CancellationToken;
 
 /// Statistics about the thread pool's current state.
@@ -17,18 +18,92 @@ pub struct PoolStats {
     pub queued: usize,
     /// Number of available slots.
     pub available: usize,
+    /// Number of slots held by running tasks (a weighted task holds several).
+    pub used: usize,
+    /// Memory reserved by running tasks, in MB.
+    pub memory_used_mb: u64,
+    /// Total memory budget in MB, if one is configured.
+    pub memory_budget_mb: Option<u64>,
+    /// The agents' own slots, once they are limited separately.
+    pub agents: Option<LaneStats>,
+}
+
+/// Statistics about the slots set aside for agents (see [`Pool::limit_agents`]).
+#[derive(Debug, Clone, Copy)]
+pub struct LaneStats {
+    pub capacity: usize,
+    pub active: usize,
+    pub queued: usize,
+}
+
+/// Resources a task needs before it may start.
+#[derive(Debug, Clone, Copy)]
+pub struct Demand {
+    /// Worker slots held while the task runs.
+    pub slots: u32,
+    /// Memory (MB) reserved from the pool's memory budget while the task runs.
+    pub memory_mb: u64,
+}
+
+impl Default for Demand {
+    fn default() -> Self {
+        Self {
+            slots: 1,
+            memory_mb: 0,
+        }
+    }
+}
+
+/// The machine's physical memory in MB, if it can be told.
+pub fn system_memory_mb() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        // SAFETY: sysconf has no preconditions; it returns -1 for unknown values.
+        let (pages, page_size) = unsafe {
+            (
+                libc::sysconf(libc::_SC_PHYS_PAGES),
+                libc::sysconf(libc::_SC_PAGESIZE),
+            )
+        };
+        let bytes = u64::try_from(pages).ok()? * u64::try_from(page_size).ok()?;
+        (bytes > 0).then_some(bytes / (1024 * 1024))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// An optional memory budget, tracked as one permit per MB.
+#[derive(Clone)]
+struct MemoryBudget {
+    semaphore: Arc<Semaphore>,
+    total_mb: u32,
+}
+
+/// Slots for agent tasks, apart from the pool's own.
+struct Lane {
+    semaphore: Arc<Semaphore>,
+    capacity: usize,
+    active: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
 }
 
 /// A fixed-size thread pool backed by a tokio semaphore.
 ///
 /// All jobs (checks, solvers) share this pool. When all slots are
-/// occupied, new jobs wait until a slot becomes available.
+/// occupied, new jobs wait until a slot becomes available. Heavy jobs can
+/// claim several slots, and an optional memory budget limits how many
+/// memory-hungry jobs run at once. Agents can be given a separate limit, so they
+/// queue apart from checks.
 #[derive(Clone)]
 pub struct Pool {
     semaphore: Arc<Semaphore>,
     capacity: usize,
     active: Arc<AtomicUsize>,
     queued: Arc<AtomicUsize>,
+    memory: Option<MemoryBudget>,
+    agents: Arc<OnceLock<Lane>>,
     cancel: CancellationToken,
 }
 
@@ -46,61 +121,216 @@ impl Pool {
             capacity,
             active: Arc::new(AtomicUsize::new(0)),
             queued: Arc::new(AtomicUsize::new(0)),
+            memory: None,
+            agents: Arc::new(OnceLock::new()),
             cancel: CancellationToken::new(),
         }
     }
 
+    /// Limit the total memory (in MB) reserved by concurrently running tasks.
+    pub fn with_memory_budget(mut self, budget_mb: Option<u64>) -> Self {
+        self.memory = budget_mb.map(|mb| {
+            let total_mb = u32::try_from(mb)
+                .unwrap_or(u32::MAX)
+                .min(Semaphore::MAX_PERMITS as u32);
+            MemoryBudget {
+                semaphore: Arc::new(Semaphore::new(total_mb as usize)),
+                total_mb,
+            }
+        });
+        self
+    }
+
     /// Spawn a task that will run when a slot is available.
     ///
     /// The task waits in the queue until a permit is acquired, then executes.
+    /// If the pool is cancelled while the task is queued, it stops waiting and runs
+    /// immediately so it can observe the cancellation and finish.
     /// Returns a JoinHandle that can be awaited for the result.
     pub fn spawn<F, T>(&self, task: F) -> tokio::task::JoinHandle<T>
     where
         F: Future<Output = T> + Send + 'static,
         T: Send + 'static,
     {
-        let semaphore = self.semaphore.clone();
+        self.spawn_with(Demand::default(), task)
+    }
+
+    /// Like [`Pool::spawn`], but the task waits until all of `demand` is available.
+    ///
+    /// Demands larger than the pool are clamped to its size so they can still run
+    /// (alone) instead of waiting forever.
+    pub fn spawn_with<F, T>(&self, demand: Demand, task: F) -> tokio::task::JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let ticket = self.enqueue();
+        let admission = self.admit(demand);
         let active = self.active.clone();
-        let queued = self.queued.clone();
 
         tokio::spawn(async move {
-            queued.fetch_add(1, Ordering::SeqCst);
-            let permit = semaphore.acquire_owned().await.unwrap();
-            queued.fetch_sub(1, Ordering::SeqCst);
-            active.fetch_add(1, Ordering::SeqCst);
+            // Counters are tracked with guards so aborted tasks still release them.
+            let admission = admission.await;
+            drop(ticket);
+            run_admitted(active, admission, task).await
+        })
+    }
+
+    /// Count a task as queued until the returned ticket is dropped.
+    ///
+    /// Used by callers that admit tasks themselves (see [`Pool::admit`]) so the pool
+    /// statistics still show everything that is waiting.
+    pub fn enqueue(&self) -> QueueTicket {
+        QueueTicket {
+            _counter: CounterGuard::increment(self.queued.clone()),
+        }
+    }
+
+    /// Wait until `demand` is available and reserve it.
+    ///
+    /// Callers that need to control admission order acquire resources with this and then
+    /// start the task with [`Pool::spawn_admitted`]. Once the pool is cancelled this
+    /// resolves immediately with an empty reservation.
+    pub fn admit(&self, demand: Demand) -> impl Future<Output = Admission> + Send + 'static {
+        let semaphore = self.semaphore.clone();
+        let slots = demand.slots.clamp(1, self.capacity as u32);
+        let memory = self.memory.clone().and_then(|budget| {
+            let mb = u32::try_from(demand.memory_mb)
+                .unwrap_or(u32::MAX)
+                .min(budget.total_mb);
+            (mb > 0).then_some((budget.semaphore, mb))
+        });
+        let cancel = self.cancel.clone();
+
+        async move {
+            let permits = tokio::select! {
+                permits = async {
+                    let slots = semaphore.acquire_many_owned(slots).await.ok()?;
+                    let memory = match memory {
+                        Some((memory, mb)) => Some(memory.acquire_many_owned(mb).await.ok()?),
+                        None => None,
+                    };
+                    Some((slots, memory))
+                } => permits,
+                _ = cancel.cancelled() => None,
+            };
+            Admission { _permits: permits }
+        }
+    }
+
+    /// Run agent tasks on `limit` slots of their own instead of the pool's, so they no
+    /// longer compete with checks and never exceed the limit. Applies to every clone of
+    /// the pool; only the first call has an effect.
+    pub fn limit_agents(&self, limit: usize) {
+        let capacity = limit.max(1);
+        let _ = self.agents.set(Lane {
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            active: Arc::new(AtomicUsize::new(0)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        });
+    }
 
-            let result = task.await;
+    /// Spawn an agent task: on the agents' own slots when they are limited separately,
+    /// otherwise like [`Pool::spawn`]. The task only waits for a slot once `ready` resolves
+    /// (say, to a lock of the caller's), and keeps what it resolved to until it finishes; while
+    /// waiting for `ready` it counts as queued but holds no slot. Once the pool is cancelled
+    /// it stops waiting for `ready`.
+    pub fn spawn_agent<R, G, F, T>(&self, ready: R, task: F) -> tokio::task::JoinHandle<T>
+    where
+        R: Future<Output = G> + Send + 'static,
+        G: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let cancel = self.cancel.clone();
+        let (ticket, active, admission): (_, _, Pin<Box<dyn Future<Output = Admission> + Send>>) =
+            match self.agents.get() {
+                Some(lane) => {
+                    let semaphore = lane.semaphore.clone();
+                    let cancel = cancel.clone();
+                    let admission = async move {
+                        let permit = tokio::select! {
+                            permit = semaphore.acquire_owned() => permit.ok(),
+                            _ = cancel.cancelled() => None,
+                        };
+                        Admission {
+                            _permits: permit.map(|permit| (permit, None)),
+                        }
+                    };
+                    (
+                        CounterGuard::increment(lane.queued.clone()),
+                        lane.active.clone(),
+                        Box::pin(admission),
+                    )
+                }
+                None => (
+                    CounterGuard::increment(self.queued.clone()),
+                    self.active.clone(),
+                    Box::pin(self.admit(Demand::default())),
+                ),
+            };
 
-            drop(permit);
-            active.fetch_sub(1, Ordering::SeqCst);
+        tokio::spawn(async move {
+            let ready = tokio::select! {
+                ready = ready => Some(ready),
+                _ = cancel.cancelled() => None,
+            };
+            let admission = admission.await;
+            drop(ticket);
+            let result = run_admitted(active, admission, task).await;
+            drop(ready);
             result
         })
     }
 
+    /// Spawn a task on resources already reserved with [`Pool::admit`].
+    pub fn spawn_admitted<F, T>(&self, admission: Admission, task: F) -> tokio::task::JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        tokio::spawn(run_admitted(self.active.clone(), admission, task))
+    }
+
     /// Get current pool statistics.
     pub fn stats(&self) -> PoolStats {
+        let available = self.semaphore.available_permits();
+        let (memory_used_mb, memory_budget_mb) = match &self.memory {
+            Some(budget) => {
+                let free = budget.semaphore.available_permits() as u64;
+                let total = u64::from(budget.total_mb);
+                (total.saturating_sub(free), Some(total))
+            }
+            None => (0, None),
+        };
         PoolStats {
             capacity: self.capacity,
             active: self.active.load(Ordering::SeqCst),
             queued: self.queued.load(Ordering::SeqCst),
-            available: self.semaphore.available_permits(),
+            available,
+            used: self.capacity.saturating_sub(available),
+            memory_used_mb,
+            memory_budget_mb,
+            agents: self.agents.get().map(|lane| LaneStats {
+                capacity: lane.capacity,
+                active: lane.active.load(Ordering::SeqCst),
+                queued: lane.queued.load(Ordering::SeqCst),
+            }),
         }
     }
 
     /// Get the cancellation token for this pool.
-    #[allow(dead_code)]
     pub fn cancel_token(&self) -> CancellationToken {
         self.cancel.clone()
     }
 
     /// Signal cancellation to all tasks that check the token.
-    #[allow(dead_code)]
     pub fn cancel(&self) {
         self.cancel.cancel();
     }
 
     /// Check if cancellation has been requested.
-    #[allow(dead_code)]
     pub fn is_cancelled(&self) -> bool {
         self.cancel.is_cancelled()
     }
@@ -112,6 +342,42 @@ impl Pool {
     }
 }
 
+/// Pool resources reserved for one task; released when dropped.
+pub struct Admission {
+    _permits: Option<(OwnedSemaphorePermit, Option<OwnedSemaphorePermit>)>,
+}
+
+/// Keeps a task counted as queued in the pool statistics while alive.
+pub struct QueueTicket {
+    _counter: CounterGuard,
+}
+
+async fn run_admitted<F, T>(active: Arc<AtomicUsize>, admission: Admission, task: F) -> T
+where
+    F: Future<Output = T>,
+{
+    let _active_guard = CounterGuard::increment(active);
+    let result = task.await;
+    drop(admission);
+    result
+}
+
+/// Increments a counter on creation and decrements it on drop.
+struct CounterGuard(Arc<AtomicUsize>);
+
+impl CounterGuard {
+    fn increment(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for CounterGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +435,164 @@ mod tests {
         assert!(pool.capacity() >= 1);
     }
 
+    #[tokio::test]
+    async fn pool_releases_slot_when_task_aborted() {
+        let pool = Pool::new(1);
+
+        let handle = pool.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().active, 1);
+
+        handle.abort();
+        let _ = handle.await;
+
+        let stats = pool.stats();
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.available, 1);
+    }
+
+    #[tokio::test]
+    async fn pool_runs_queued_tasks_once_cancelled() {
+        let pool = Pool::new(1);
+
+        let blocker = pool.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let token = pool.cancel_token();
+        let queued = pool.spawn(async move { token.is_cancelled() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+
+        pool.cancel();
+        let saw_cancel = tokio::time::timeout(Duration::from_secs(1), queued)
+            .await
+            .expect("queued task should run after cancellation")
+            .unwrap();
+        assert!(saw_cancel);
+
+        blocker.abort();
+    }
+
+    #[tokio::test]
+    async fn weighted_task_holds_several_slots() {
+        let pool = Pool::new(4);
+
+        let heavy = pool.spawn_with(
+            Demand {
+                slots: 3,
+                memory_mb: 0,
+            },
+            async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            },
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let stats = pool.stats();
+        assert_eq!(stats.active, 1);
+        assert_eq!(stats.used, 3);
+
+        // A second heavy task must wait for the first one.
+        let queued = pool.spawn_with(
+            Demand {
+                slots: 2,
+                memory_mb: 0,
+            },
+            async {},
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+
+        heavy.abort();
+        tokio::time::timeout(Duration::from_secs(1), queued)
+            .await
+            .expect("queued task should start once slots are released")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_demand_is_clamped_to_pool() {
+        let pool = Pool::new(2).with_memory_budget(Some(1024));
+        let handle = pool.spawn_with(
+            Demand {
+                slots: 8,
+                memory_mb: 4096,
+            },
+            async { 7 },
+        );
+        let result = tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("oversized task should still run")
+            .unwrap();
+        assert_eq!(result, 7);
+    }
+
+    #[tokio::test]
+    async fn memory_budget_limits_concurrency() {
+        let pool = Pool::new(8).with_memory_budget(Some(4096));
+        let demand = Demand {
+            slots: 1,
+            memory_mb: 3072,
+        };
+
+        let first = pool.spawn_with(demand, async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let stats = pool.stats();
+        assert_eq!(stats.memory_used_mb, 3072);
+        assert_eq!(stats.memory_budget_mb, Some(4096));
+
+        let second = pool.spawn_with(demand, async {});
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+
+        first.abort();
+        tokio::time::timeout(Duration::from_secs(1), second)
+            .await
+            .expect("second task should start once memory is released")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn agents_queue_on_their_own_slots() {
+        let pool = Pool::new(1);
+        let check = pool.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        // Without a separate limit, agents wait for the pool like anything else.
+        let waiting = pool.spawn_agent(std::future::ready(()), async {});
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.stats().queued, 1);
+        assert!(pool.stats().agents.is_none());
+        waiting.abort();
+
+        pool.limit_agents(2);
+        let mut agents: Vec<_> = (0..3)
+            .map(|_| {
+                pool.spawn_agent(std::future::ready(()), async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let lane = pool.stats().agents.expect("agents have their own slots");
+        assert_eq!((lane.capacity, lane.active, lane.queued), (2, 2, 1));
+
+        agents.remove(0).abort();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let lane = pool.stats().agents.unwrap();
+        assert_eq!((lane.active, lane.queued), (2, 0));
+
+        check.abort();
+        for agent in agents {
+            agent.abort();
+        }
+    }
+
     #[tokio::test]
     async fn pool_cancellation_token_works() {
         let pool = Pool::new(2);
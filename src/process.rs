@@ -1,154 +1,1147 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use tempfile::TempPath;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
 use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
 use tokio::time;
+use tokio_util::sync::CancellationToken;
 
-use crate::config::CommandSpec;
+use crate::config::{CommandSpec, Environment, PtySize};
 use crate::error::ProcessError;
+use crate::redact::Redactor;
 use crate::ui::{StreamType, UiEvent, sanitize_text_for_tui};
 
-/// Run a command with optional stdin, collecting stdout/stderr, honoring timeout, and killing on timeout.
+/// Default time between SIGTERM and SIGKILL when stopping a process tree.
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// How long output readers may keep draining after the process exits. Background
+/// grandchildren can hold the pipes open indefinitely, so this bounds the wait.
+const READER_DRAIN: Duration = Duration::from_secs(1);
+
+/// The most of a line read into memory at once; longer lines are split into pieces of this
+/// size, so a huge line without a newline can't get around the output size limit.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// How much of a line cut short is at least held back and redacted along with the rest of
+/// the line, so that a secret across the cut is still found whole.
+const REDACT_OVERLAP: usize = 4 * 1024;
+
+/// When a process must be stopped and how.
+#[derive(Debug, Clone)]
+pub struct StopPolicy {
+    pub timeout: Option<Duration>,
+    /// Time between SIGTERM and SIGKILL when stopping the process tree.
+    pub kill_grace: Duration,
+    /// Stops the process (as if it timed out) once cancelled.
+    pub cancel: Option<CancellationToken>,
+}
+
+impl StopPolicy {
+    pub fn new(timeout: Option<Duration>, kill_grace: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            kill_grace: kill_grace.unwrap_or(DEFAULT_KILL_GRACE),
+            cancel: None,
+        }
+    }
+
+    /// Also stop the process when `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
+/// Where a command's output goes besides the returned buffers, and how much of it is kept.
+#[derive(Debug, Clone, Default)]
+pub struct OutputSink {
+    /// Stream output lines to the UI under this source name.
+    pub stream: Option<(String, Sender<UiEvent>)>,
+    /// Keep at most this many bytes of each stream in memory (its head and tail).
+    pub max_bytes: Option<usize>,
+    /// Write the full, untruncated output (both streams) to this file.
+    pub log_file: Option<PathBuf>,
+    /// Run under a pseudo-terminal of this size (Unix only). Stdout and stderr arrive
+    /// merged, in order, as stdout, cleaned of terminal control sequences.
+    pub pty: Option<PtySize>,
+    /// Mask secrets in each line before it is kept, logged or streamed.
+    pub redactor: Option<Arc<Redactor>>,
+}
+
+/// One piece of a command's output (a line, usually), in the order it was read.
+#[derive(Debug, Clone)]
+pub struct OutputChunk {
+    pub stream: StreamType,
+    /// Time since the command started.
+    pub at: Duration,
+    pub bytes: Vec<u8>,
+}
+
+/// Output dropped from the middle to stay within the size limit.
+#[derive(Debug, Clone)]
+pub struct OutputGap {
+    /// Index of the first chunk after the gap.
+    pub index: usize,
+    pub bytes: usize,
+    /// Where the full output was written, if anywhere.
+    pub log_file: Option<PathBuf>,
+}
+
+/// Everything a command wrote to stdout and stderr, interleaved in arrival order.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub chunks: Vec<OutputChunk>,
+    pub gap: Option<OutputGap>,
+    /// A private temporary file with the complete output, when some of it was dropped from
+    /// memory; removed when the last copy of the output is dropped.
+    spill: Option<Arc<TempPath>>,
+}
+
+/// What a command reads on stdin.
+#[derive(Debug)]
+pub enum Input {
+    Bytes(Vec<u8>),
+    File(std::fs::File),
+}
+
+impl CommandOutput {
+    /// Both streams interleaved, with a marker where output was dropped.
+    pub fn text(&self) -> String {
+        self.render(None)
+    }
+
+    /// The lines of both streams interleaved and complete, including output dropped from
+    /// memory, for parsers that must not miss any of it. Dropped output is read back from
+    /// disk a line at a time (split like huge lines are while reading); if it can't be, the
+    /// lines of [`Self::text`] stand in.
+    pub fn lines(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        if let Some(spill) = &self.spill
+            && let Ok(file) = std::fs::File::open(spill.as_ref())
+        {
+            let mut reader = std::io::BufReader::new(file);
+            let lines = std::iter::from_fn(move || {
+                let mut line = Vec::new();
+                let read = std::io::Read::take(&mut reader, MAX_LINE_BYTES as u64)
+                    .read_until(b'\n', &mut line);
+                if read.ok()? == 0 {
+                    return None;
+                }
+                if line.ends_with(b"\n") {
+                    line.pop();
+                }
+                Some(String::from_utf8_lossy(&line).into_owned())
+            });
+            return Box::new(lines);
+        }
+        let text = self.text();
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        Box::new(lines.into_iter())
+    }
+
+    /// The complete output as input for another command (a formatter), straight from disk
+    /// when some of it was dropped from memory.
+    pub fn full_input(&self) -> Input {
+        match self
+            .spill
+            .as_ref()
+            .and_then(|spill| std::fs::File::open(spill.as_ref()).ok())
+        {
+            Some(file) => Input::File(file),
+            None => Input::Bytes(self.text().into_bytes()),
+        }
+    }
+
+    /// A single stream on its own, for parsers that only understand one of them.
+    pub fn stream_text(&self, stream: StreamType) -> String {
+        self.render(Some(stream))
+    }
+
+    fn render(&self, only: Option<StreamType>) -> String {
+        let mut out = Vec::new();
+        for (idx, chunk) in self.chunks.iter().enumerate() {
+            if let Some(gap) = self.gap.as_ref().filter(|gap| gap.index == idx) {
+                out.extend_from_slice(gap.marker().as_bytes());
+            }
+            if only.is_none_or(|stream| stream == chunk.stream) {
+                out.extend_from_slice(&chunk.bytes);
+            }
+        }
+        if let Some(gap) = self
+            .gap
+            .as_ref()
+            .filter(|gap| gap.index >= self.chunks.len())
+        {
+            out.extend_from_slice(gap.marker().as_bytes());
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+impl OutputChunk {
+    /// Write the chunk as a log line prefixed with its timestamp and stream.
+    fn write_log(&self, log: &mut impl Write) -> std::io::Result<()> {
+        let tag = match self.stream {
+            StreamType::Stdout => "out",
+            StreamType::Stderr => "err",
+        };
+        write!(log, "[{:>9.3}s {tag}] ", self.at.as_secs_f64())?;
+        log.write_all(&self.bytes)?;
+        if !self.bytes.ends_with(b"\n") {
+            log.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputGap {
+    fn marker(&self) -> String {
+        let log = match &self.log_file {
+            Some(path) => format!("; full log: {}", path.display()),
+            None => String::new(),
+        };
+        format!("\n[... {} bytes omitted{log} ...]\n", self.bytes)
+    }
+}
+
+/// Run a command with optional stdin, collecting its output, honoring timeout, and killing on timeout.
 pub async fn run_command(
     spec: &CommandSpec,
-    env: &HashMap<String, String>,
+    env: &Environment,
     root: &Path,
-    timeout: Option<Duration>,
-    stdin: Option<Vec<u8>>,
-) -> Result<(Option<i32>, Vec<u8>, Vec<u8>)> {
-    run_command_streaming(spec, env, root, timeout, stdin, None, None).await
+    stop: StopPolicy,
+    stdin: Option<Input>,
+) -> Result<(Option<i32>, CommandOutput)> {
+    run_command_streaming(spec, env, root, stop, stdin, OutputSink::default()).await
 }
-
 /// Run a command with optional streaming of output lines.
+///
+/// On Unix the command runs in its own process group so that a timeout stops the
+/// whole tree (shell wrappers, `cargo test` binaries, `npm run` scripts), not just
+/// the direct child. With a pseudo-terminal it gets its own session instead, which
+/// has the same effect.
 pub async fn run_command_streaming(
     spec: &CommandSpec,
-    env: &HashMap<String, String>,
+    env: &Environment,
     root: &Path,
-    timeout: Option<Duration>,
-    stdin: Option<Vec<u8>>,
-    source_name: Option<String>,
-    ui_tx: Option<Sender<UiEvent>>,
-) -> Result<(Option<i32>, Vec<u8>, Vec<u8>)> {
+    stop: StopPolicy,
+    stdin: Option<Input>,
+    output: OutputSink,
+) -> Result<(Option<i32>, CommandOutput)> {
     let wants_stdin = stdin.is_some();
+    let (stdin_cfg, stdin) = match stdin {
+        // A background process group that reads the terminal gets SIGTTIN and hangs,
+        // so commands without input get an empty stdin instead of ours.
+        None => (Stdio::null(), None),
+        Some(Input::Bytes(bytes)) => (Stdio::piped(), Some(bytes)),
+        Some(Input::File(file)) => (Stdio::from(file), None),
+    };
     let mut cmd = Command::new(&spec.program);
+    if env.clear {
+        cmd.env_clear();
+    }
     cmd.args(&spec.args)
-        .envs(env)
+        .envs(&env.vars)
         .current_dir(root)
-        .stdin(if wants_stdin {
-            Stdio::piped()
-        } else {
-            Stdio::inherit()
-        })
+        .stdin(stdin_cfg)
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+        .stderr(Stdio::piped())
+        // Dropping the future (e.g. an aborted watch-mode run) must not leave the child running.
+        .kill_on_drop(true);
+
+    #[cfg(unix)]
+    let pty = match output.pty {
+        Some(size) => Some(
+            crate::pty::Pty::open(size)
+                .and_then(|pty| pty.attach(&mut cmd, wants_stdin))
+                .map_err(|e| {
+                    ProcessError::SpawnFailed(format!("failed to open pseudo-terminal: {e}"))
+                })?,
+        ),
+        None => {
+            cmd.process_group(0);
+            None
+        }
+    };
+    #[cfg(not(unix))]
+    let pty: Option<tokio::process::ChildStdout> = None;
+    let merged = pty.is_some();
 
     let mut child = cmd
         .spawn()
         .map_err(|e| ProcessError::SpawnFailed(e.to_string()))?;
+    // Close our copies of the terminal so its reader sees EOF once the command exits.
+    drop(cmd);
+    let mut group = ProcessGroupGuard::new(&child);
 
-    if let Some(input) = stdin
-        && let Some(mut child_stdin) = child.stdin.take()
-    {
-        child_stdin
-            .write_all(&input)
-            .await
-            .map_err(|e| ProcessError::StdinWriteFailed(e.to_string()))?;
-        child_stdin
-            .shutdown()
-            .await
-            .map_err(|e| ProcessError::StdinWriteFailed(e.to_string()))?;
-    }
-
-    // Stream stdout
-    let stdout_handle = {
-        let stdout = child.stdout.take();
-        let ui_tx = ui_tx.clone();
-        let source = source_name.clone();
-        tokio::spawn(async move {
-            let mut buf = Vec::new();
-            if let Some(out) = stdout {
-                if let (Some(tx), Some(src)) = (ui_tx, source) {
-                    // Stream lines as they come
-                    let mut reader = BufReader::new(out);
-                    let mut line = String::new();
-                    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                        let trimmed = sanitize_text_for_tui(line.trim_end());
-                        buf.extend_from_slice(line.as_bytes());
-                        let _ = tx
-                            .send(UiEvent::StreamLine {
-                                source: src.clone(),
-                                stream: StreamType::Stdout,
-                                line: trimmed,
-                            })
-                            .await;
-                        line.clear();
-                    }
-                } else {
-                    // No streaming, just collect
-                    let mut out = out;
-                    let _ = out.read_to_end(&mut buf).await;
-                }
-            }
-            buf
-        })
+    // The log is best-effort: if it can't be created the output is still captured in memory.
+    let log = output.log_file.as_deref().and_then(|path| {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).ok()?;
+        }
+        std::fs::File::create(path).ok()
+    });
+    let log_path = log.as_ref().and(output.log_file);
+    let capture = Arc::new(Mutex::new(Capture::new(
+        output.max_bytes,
+        log,
+        log_path,
+        merged,
+    )));
+
+    let stdout = match pty {
+        Some(reader) => spawn_reader(
+            Some(reader),
+            StreamType::Stdout,
+            capture.clone(),
+            output.stream.clone(),
+            output.redactor.clone(),
+        ),
+        None => spawn_reader(
+            child.stdout.take(),
+            StreamType::Stdout,
+            capture.clone(),
+            output.stream.clone(),
+            output.redactor.clone(),
+        ),
     };
+    let stderr = spawn_reader(
+        child.stderr.take(),
+        StreamType::Stderr,
+        capture.clone(),
+        output.stream,
+        output.redactor,
+    );
+    // Input goes in from a task of its own once the readers run: a command that fills its
+    // output before reading all of its input would otherwise block the write, out of reach
+    // of the timeout and cancellation below.
+    let feeder = stdin.and_then(|input| {
+        let mut child_stdin = child.stdin.take()?;
+        Some(tokio::spawn(async move {
+            child_stdin.write_all(&input).await?;
+            child_stdin.shutdown().await
+        }))
+    });
 
-    // Stream stderr
-    let stderr_handle = {
-        let stderr = child.stderr.take();
-        let ui_tx = ui_tx;
-        let source = source_name;
-        tokio::spawn(async move {
-            let mut buf = Vec::new();
-            if let Some(err) = stderr {
-                if let (Some(tx), Some(src)) = (ui_tx, source) {
-                    // Stream lines as they come
-                    let mut reader = BufReader::new(err);
-                    let mut line = String::new();
-                    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                        let trimmed = sanitize_text_for_tui(line.trim_end());
-                        buf.extend_from_slice(line.as_bytes());
-                        let _ = tx
-                            .send(UiEvent::StreamLine {
-                                source: src.clone(),
-                                stream: StreamType::Stderr,
-                                line: trimmed,
-                            })
-                            .await;
-                        line.clear();
-                    }
-                } else {
-                    // No streaming, just collect
-                    let mut err = err;
-                    let _ = err.read_to_end(&mut buf).await;
-                }
+    let outcome = tokio::select! {
+        res = child.wait() => Ok(res.map_err(|e| ProcessError::OutputReadFailed(e.to_string()))?),
+        dur = sleep_for(stop.timeout) => Err(ProcessError::Timeout(dur)),
+        _ = cancelled(stop.cancel.as_ref()) => Err(ProcessError::Cancelled),
+    };
+    let status = match outcome {
+        Ok(status) => status,
+        Err(err) => {
+            if let Some(feeder) = &feeder {
+                feeder.abort();
             }
-            buf
-        })
+            terminate(&mut child, stop.kill_grace).await;
+            group.disarm();
+            finish_reader(stdout).await;
+            finish_reader(stderr).await;
+            return Err(err.into());
+        }
     };
+    // Background processes it started on purpose (servers, daemons) may be needed later.
+    group.disarm();
+
+    finish_reader(stdout).await;
+    finish_reader(stderr).await;
+    if let Some(feeder) = feeder {
+        finish_feeder(feeder).await?;
+    }
+    let output = capture.lock().unwrap_or_else(|e| e.into_inner()).finish();
+
+    Ok((status.code(), output))
+}
+
+/// Sleep for the timeout and return it; never completes without one.
+async fn sleep_for(timeout: Option<Duration>) -> Duration {
+    match timeout {
+        Some(dur) => {
+            time::sleep(dur).await;
+            dur
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Complete once the token is cancelled; never completes without one.
+async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Output collected from both streams. Over the size limit only the head and tail are
+/// kept in memory; the full output still goes to the log file, if there is one.
+struct Capture {
+    /// Output from a pseudo-terminal, cleaned up before it is kept.
+    terminal: bool,
+    started: Instant,
+    head: Vec<OutputChunk>,
+    head_bytes: usize,
+    tail: VecDeque<OutputChunk>,
+    tail_bytes: usize,
+    omitted: usize,
+    limit: Option<usize>,
+    log: Option<std::fs::File>,
+    log_path: Option<PathBuf>,
+    /// Everything so far, once output had to be dropped from memory.
+    spill: Option<(std::fs::File, TempPath)>,
+}
 
-    let status = if let Some(dur) = timeout {
-        match time::timeout(dur, child.wait()).await {
-            Ok(res) => res.map_err(|e| ProcessError::OutputReadFailed(e.to_string()))?,
-            Err(_) => {
-                let _ = child.kill().await;
-                return Err(ProcessError::Timeout(dur).into());
+impl Capture {
+    fn new(
+        limit: Option<usize>,
+        log: Option<std::fs::File>,
+        log_path: Option<PathBuf>,
+        terminal: bool,
+    ) -> Self {
+        Self {
+            terminal,
+            started: Instant::now(),
+            head: Vec::new(),
+            head_bytes: 0,
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            omitted: 0,
+            limit,
+            log,
+            log_path,
+            spill: None,
+        }
+    }
+
+    fn push(&mut self, stream: StreamType, bytes: &[u8]) {
+        let at = self.started.elapsed();
+        let chunk = |bytes: &[u8]| OutputChunk {
+            stream,
+            at,
+            bytes: bytes.to_vec(),
+        };
+        if let Some(log) = &mut self.log {
+            let _ = chunk(bytes).write_log(log);
+        }
+        // Terminal output is full of colors, cursor movement and CRLF line endings.
+        let cleaned;
+        let bytes = if self.terminal {
+            cleaned = sanitize_text_for_tui(&String::from_utf8_lossy(bytes)).into_bytes();
+            &cleaned[..]
+        } else {
+            bytes
+        };
+        if let Some((spill, _)) = &mut self.spill {
+            let _ = spill.write_all(bytes);
+        }
+
+        let Some(limit) = self.limit else {
+            self.head.push(chunk(bytes));
+            return;
+        };
+        let head_cap = limit / 2;
+        let tail_cap = limit - head_cap;
+        let take = head_cap.saturating_sub(self.head_bytes).min(bytes.len());
+        if take > 0 {
+            self.head.push(chunk(&bytes[..take]));
+            self.head_bytes += take;
+        }
+        let rest = &bytes[take..];
+        if rest.is_empty() {
+            return;
+        }
+        self.tail.push_back(chunk(rest));
+        self.tail_bytes += rest.len();
+        if self.tail_bytes > tail_cap && self.spill.is_none() && self.omitted == 0 {
+            self.start_spill();
+        }
+        while self.tail_bytes > tail_cap {
+            let excess = self.tail_bytes - tail_cap;
+            let Some(front) = self.tail.front_mut() else {
+                break;
+            };
+            let dropped = excess.min(front.bytes.len());
+            if dropped == front.bytes.len() {
+                self.tail.pop_front();
+            } else {
+                front.bytes.drain(..dropped);
             }
+            self.tail_bytes -= dropped;
+            self.omitted += dropped;
+        }
+    }
+
+    /// About to drop output from memory: write out everything kept so far, and from now on
+    /// every new chunk too. Without a spill file only the head and tail can be parsed. The
+    /// file is only readable by us, since the output may hold secrets the redactor missed.
+    fn start_spill(&mut self) {
+        let Ok(file) = tempfile::Builder::new()
+            .prefix("scanner-rs-output-")
+            .tempfile()
+        else {
+            return;
+        };
+        let (mut file, path) = file.into_parts();
+        let written = self
+            .head
+            .iter()
+            .chain(&self.tail)
+            .try_for_each(|chunk| file.write_all(&chunk.bytes));
+        // On error the path is dropped, which removes the file.
+        if written.is_ok() {
+            self.spill = Some((file, path));
         }
-    } else {
-        child
-            .wait()
-            .await
-            .map_err(|e| ProcessError::OutputReadFailed(e.to_string()))?
+    }
+
+    /// The kept output, noting where bytes were dropped.
+    fn finish(&mut self) -> CommandOutput {
+        let mut chunks = std::mem::take(&mut self.head);
+        let gap = (self.omitted > 0).then(|| OutputGap {
+            index: chunks.len(),
+            bytes: self.omitted,
+            log_file: self.log_path.clone(),
+        });
+        chunks.extend(std::mem::take(&mut self.tail));
+        let spill = self.spill.take().map(|(_, path)| Arc::new(path));
+        CommandOutput { chunks, gap, spill }
+    }
+}
+
+/// Wait (bounded) for a reader to reach EOF.
+async fn finish_reader(mut handle: JoinHandle<()>) {
+    if time::timeout(READER_DRAIN, &mut handle).await.is_err() {
+        handle.abort();
+    }
+}
+
+/// Check how writing stdin went once the command exited. A command may exit without reading
+/// all of its input, and a background process may keep stdin open without reading it; neither
+/// is an error.
+async fn finish_feeder(mut handle: JoinHandle<std::io::Result<()>>) -> Result<(), ProcessError> {
+    match time::timeout(READER_DRAIN, &mut handle).await {
+        Ok(Ok(Err(e))) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+            Err(ProcessError::StdinWriteFailed(e.to_string()))
+        }
+        Ok(_) => Ok(()),
+        Err(_) => {
+            handle.abort();
+            Ok(())
+        }
+    }
+}
+
+/// Where to cut `piece`, a line cut short, so that at least [`REDACT_OVERLAP`] bytes are
+/// left after it and no secret is cut in two. Something as long as the whole piece is cut
+/// anyway; a piece too short to leave that much isn't cut at all.
+fn safe_cut(redactor: &Redactor, piece: &[u8]) -> usize {
+    let text = match std::str::from_utf8(piece) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&piece[..e.valid_up_to()]).unwrap_or_default(),
     };
+    let mut at = piece.len().saturating_sub(REDACT_OVERLAP).min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    match redactor.cut_before(text, at) {
+        _ if at == 0 => piece.len(),
+        0 => at,
+        cut => cut,
+    }
+}
+
+/// Read one stream line by line into the shared capture, streaming lines to the UI if asked.
+fn spawn_reader<R>(
+    reader: Option<R>,
+    stream: StreamType,
+    capture: Arc<Mutex<Capture>>,
+    ui_stream: Option<(String, Sender<UiEvent>)>,
+    redactor: Option<Arc<Redactor>>,
+) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let Some(reader) = reader else {
+            return;
+        };
+        // Lines keep chunks meaningful when both streams are interleaved.
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        let mut redact_lines = redactor.as_deref().map(Redactor::lines);
+        // The end of a line cut short, read again with what follows.
+        let mut held = Vec::new();
+        loop {
+            let room = MAX_LINE_BYTES - held.len();
+            line.append(&mut held);
+            let read = (&mut reader)
+                .take(room as u64)
+                .read_until(b'\n', &mut line)
+                .await
+                .unwrap_or(0);
+            if line.is_empty() {
+                break;
+            }
+            // A secret may run across the cut: keep back the end of the piece, from where it
+            // may start, to be redacted whole with the next one.
+            if read > 0
+                && !line.ends_with(b"\n")
+                && let Some(redactor) = redactor.as_deref()
+            {
+                let cut = safe_cut(redactor, &line);
+                held = line.split_off(cut);
+            }
+            if let Some(lines) = redact_lines.as_mut()
+                && let Cow::Owned(redacted) = lines.redact(&String::from_utf8_lossy(&line))
+            {
+                line = redacted.into_bytes();
+            }
+            capture
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(stream, &line);
+            if let Some((src, tx)) = &ui_stream {
+                let trimmed = sanitize_text_for_tui(String::from_utf8_lossy(&line).trim_end());
+                let _ = tx
+                    .send(UiEvent::StreamLine {
+                        source: src.clone(),
+                        stream,
+                        line: trimmed,
+                    })
+                    .await;
+            }
+            line.clear();
+        }
+    })
+}
+
+/// Stop a child and everything it spawned: SIGTERM to the process group, then
+/// SIGKILL once the grace period expires.
+async fn terminate(child: &mut Child, grace: Duration) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let pgid = pid as libc::pid_t;
+        signal_group(pgid, libc::SIGTERM);
+
+        let deadline = time::Instant::now() + grace;
+        loop {
+            // Reap the leader so it doesn't keep the group alive as a zombie.
+            let _ = child.try_wait();
+            if !group_alive(pgid) {
+                return;
+            }
+            if time::Instant::now() >= deadline {
+                break;
+            }
+            time::sleep(Duration::from_millis(25)).await;
+        }
+
+        signal_group(pgid, libc::SIGKILL);
+        let _ = child.wait().await;
+        return;
+    }
+
+    let _ = grace;
+    let _ = child.kill().await;
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: libc::pid_t, signal: libc::c_int) {
+    // SAFETY: kill(2) has no memory-safety preconditions; a negative pid targets the group.
+    unsafe {
+        libc::kill(-pgid, signal);
+    }
+}
+
+#[cfg(unix)]
+fn group_alive(pgid: libc::pid_t) -> bool {
+    // SAFETY: signal 0 only checks for existence/permission.
+    unsafe { libc::kill(-pgid, 0) == 0 }
+}
 
-    let stdout = stdout_handle.await.unwrap_or_default();
-    let stderr = stderr_handle.await.unwrap_or_default();
+/// Kills the child's process group if the run is abandoned before the child exits
+/// (e.g. the surrounding task is aborted). `kill_on_drop` only covers the direct child.
+struct ProcessGroupGuard {
+    #[cfg(unix)]
+    pgid: Option<libc::pid_t>,
+}
 
-    Ok((status.code(), stdout, stderr))
+impl ProcessGroupGuard {
+    fn new(child: &Child) -> Self {
+        #[cfg(unix)]
+        {
+            Self {
+                pgid: child.id().map(|pid| pid as libc::pid_t),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = child;
+            Self {}
+        }
+    }
+
+    fn disarm(&mut self) {
+        #[cfg(unix)]
+        {
+            self.pgid = None;
+        }
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            signal_group(pgid, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+    fn sh(script: &str) -> CommandSpec {
+        CommandSpec {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+        }
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        std::env::temp_dir().join(format!("scanner-rs-{name}-{}-{nanos}", std::process::id()))
+    }
+
+    /// Zombies (reparented children the sandbox's init hasn't reaped) count as dead.
+    fn process_alive(pid: i32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            Ok(stat) => !stat
+                .rsplit_once(')')
+                .is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')),
+            // SAFETY: signal 0 only checks for existence.
+            Err(_) => unsafe { libc::kill(pid, 0) == 0 },
+        }
+    }
+
+    async fn read_pid(path: &Path) -> i32 {
+        for _ in 0..100 {
+            if let Ok(text) = std::fs::read_to_string(path)
+                && let Ok(pid) = text.trim().parse()
+            {
+                return pid;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("pid file {} never written", path.display());
+    }
+
+    async fn wait_dead(pid: i32) -> bool {
+        for _ in 0..100 {
+            if !process_alive(pid) {
+                return true;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_grandchildren() {
+        let pid_file = temp_file("grandchild-pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let stop = StopPolicy::new(
+            Some(Duration::from_millis(300)),
+            Some(Duration::from_millis(200)),
+        );
+
+        let started = Instant::now();
+        let err = run_command(
+            &sh(&script),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            None,
+        )
+        .await
+        .expect_err("expected timeout");
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let pid = read_pid(&pid_file).await;
+        assert!(wait_dead(pid).await, "grandchild {pid} still running");
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn input_is_written_while_output_is_read() {
+        // Fills its stdout pipe before it reads any input.
+        let script = "head -c 1000000 /dev/zero; wc -c";
+        let stop = StopPolicy::new(Some(Duration::from_secs(10)), None);
+        let input = Input::Bytes(vec![b'x'; 1_000_000]);
+
+        let (code, output) = run_command(
+            &sh(script),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            Some(input),
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert!(output.text().trim_end().ends_with("1000000"));
+    }
+
+    #[tokio::test]
+    async fn timeout_stops_a_command_that_never_reads_its_input() {
+        let stop = StopPolicy::new(
+            Some(Duration::from_millis(300)),
+            Some(Duration::from_millis(200)),
+        );
+        let input = Input::Bytes(vec![b'x'; 1_000_000]);
+
+        let started = Instant::now();
+        let err = run_command(
+            &sh("sleep 30"),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            Some(input),
+        )
+        .await
+        .expect_err("expected timeout");
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn timeout_escalates_to_sigkill_after_grace() {
+        let pid_file = temp_file("stubborn-pid");
+        let script = format!(
+            "sh -c 'trap \"\" TERM; echo $$ > {}; sleep 30' & wait",
+            pid_file.display()
+        );
+        let stop = StopPolicy::new(
+            Some(Duration::from_millis(300)),
+            Some(Duration::from_millis(200)),
+        );
+
+        let started = Instant::now();
+        let _ = run_command(
+            &sh(&script),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            None,
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let pid = read_pid(&pid_file).await;
+        assert!(
+            wait_dead(pid).await,
+            "TERM-ignoring grandchild {pid} survived"
+        );
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_process_tree() {
+        let pid_file = temp_file("cancelled-pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let token = CancellationToken::new();
+        let stop =
+            StopPolicy::new(None, Some(Duration::from_millis(200))).with_cancel(token.clone());
+
+        let canceller = {
+            let pid_file = pid_file.clone();
+            tokio::spawn(async move {
+                read_pid(&pid_file).await;
+                token.cancel();
+            })
+        };
+
+        let started = Instant::now();
+        let err = run_command(
+            &sh(&script),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            None,
+        )
+        .await
+        .expect_err("expected cancellation");
+        assert!(matches!(
+            err.downcast_ref::<ProcessError>(),
+            Some(ProcessError::Cancelled)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let _ = canceller.await;
+
+        let pid = read_pid(&pid_file).await;
+        assert!(wait_dead(pid).await, "grandchild {pid} still running");
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn large_output_keeps_head_and_tail_and_spills_to_log() {
+        let log_file = temp_file("spill").join("check.log");
+        let output = OutputSink {
+            stream: None,
+            max_bytes: Some(100),
+            log_file: Some(log_file.clone()),
+            pty: None,
+            redactor: None,
+        };
+        let (code, output) = run_command_streaming(
+            &sh("echo first; seq 1 10000; echo last"),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+            output,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+
+        let text = output.text();
+        assert!(text.starts_with("first\n"));
+        assert!(text.trim_end().ends_with("last"));
+        assert!(text.contains("bytes omitted"));
+        assert!(text.contains(&log_file.display().to_string()));
+        assert!(text.len() < 300);
+
+        let full = std::fs::read_to_string(&log_file).unwrap();
+        assert!(full.contains("out] 5000\n"));
+
+        // Parsers still see the dropped middle, read back from a spill file.
+        let lines: Vec<String> = output.lines().collect();
+        assert_eq!(lines.len(), 10002);
+        assert_eq!(lines[..3], ["first", "1", "2"]);
+        assert_eq!(lines[5000], "5000");
+        assert_eq!(lines[10001], "last");
+        let spill = output.spill.as_ref().unwrap().to_path_buf();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&spill).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0, "spill file is readable by others");
+        }
+        let Input::File(mut input) = output.full_input() else {
+            panic!("expected the spill file as input");
+        };
+        let mut full = String::new();
+        std::io::Read::read_to_string(&mut input, &mut full).unwrap();
+        assert!(full.ends_with("10000\nlast\n"));
+        drop(output);
+        assert!(!spill.exists());
+        let _ = std::fs::remove_dir_all(log_file.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn huge_lines_are_split_to_stay_within_the_limit() {
+        let output = OutputSink {
+            max_bytes: Some(100),
+            ..OutputSink::default()
+        };
+        let (code, output) = run_command_streaming(
+            &sh("head -c 1000000 /dev/zero | tr '\\0' a"),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+            output,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert_eq!(output.gap.as_ref().unwrap().bytes, 1_000_000 - 100);
+        let lines: Vec<usize> = output.lines().map(|line| line.len()).collect();
+        assert!(lines.iter().all(|len| *len <= MAX_LINE_BYTES));
+        assert_eq!(lines.iter().sum::<usize>(), 1_000_000);
+    }
+
+    #[tokio::test]
+    async fn secrets_across_the_cut_of_a_huge_line_are_redacted() {
+        let token = format!("ghp_{}", "A1b2C3d4E5".repeat(4));
+        let script = format!(
+            "head -c {} /dev/zero | tr '\\0' a; echo ' {token} end'",
+            MAX_LINE_BYTES - 20
+        );
+        let output = OutputSink {
+            redactor: Some(Arc::new(Redactor::new(&Default::default()))),
+            ..OutputSink::default()
+        };
+        let (_, output) = run_command_streaming(
+            &sh(&script),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+            output,
+        )
+        .await
+        .expect("run");
+        let text = output.text();
+        assert!(!text.contains(&token[..10]), "token leaked");
+        assert!(text.ends_with(" [redacted:github-token] end\n"));
+    }
+
+    #[tokio::test]
+    async fn pty_merges_streams_and_strips_control_sequences() {
+        let output = OutputSink {
+            pty: Some(PtySize {
+                columns: 100,
+                rows: 30,
+            }),
+            ..OutputSink::default()
+        };
+        let (code, output) = run_command_streaming(
+            &sh("test -t 1 && echo tty; echo err >&2; stty size; printf '\\033[31mred\\033[0m\\n'"),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(Some(Duration::from_secs(10)), None),
+            None,
+            output,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert_eq!(output.text(), "tty\nerr\n30 100\nred\n");
+        assert!(output.stream_text(StreamType::Stderr).is_empty());
+    }
+
+    #[test]
+    fn capture_under_limit_is_untouched() {
+        let mut capture = Capture::new(Some(16), None, None, false);
+        capture.push(StreamType::Stdout, b"hello ");
+        capture.push(StreamType::Stderr, b"world");
+        let output = capture.finish();
+        assert!(output.gap.is_none());
+        assert_eq!(output.text(), "hello world");
+    }
+
+    #[tokio::test]
+    async fn streams_stay_interleaved_in_order() {
+        let (code, output) = run_command(
+            &sh("echo one; sleep 0.1; echo two >&2; sleep 0.1; echo three"),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert_eq!(output.text(), "one\ntwo\nthree\n");
+        assert_eq!(output.stream_text(StreamType::Stdout), "one\nthree\n");
+        assert_eq!(output.stream_text(StreamType::Stderr), "two\n");
+        assert!(output.chunks[1].at >= output.chunks[0].at);
+    }
+
+    #[tokio::test]
+    async fn cleared_environment_only_has_given_vars() {
+        let env = Environment {
+            clear: true,
+            vars: [("ONLY", "this")]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .into(),
+        };
+        let (code, output) = run_command(
+            &sh("echo ${ONLY}-${HOME-unset}"),
+            &env,
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert_eq!(output.text(), "this-unset\n");
+    }
+
+    #[tokio::test]
+    async fn secrets_are_redacted_before_capture() {
+        let log_file = temp_file("redact").join("check.log");
+        let output = OutputSink {
+            log_file: Some(log_file.clone()),
+            redactor: Some(Arc::new(Redactor::new(&Default::default()))),
+            ..OutputSink::default()
+        };
+        let (_, output) = run_command_streaming(
+            &sh("echo connecting to postgres://app:hunter2@db/app"),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+            output,
+        )
+        .await
+        .expect("run");
+        assert_eq!(
+            output.text(),
+            "connecting to postgres://app:[redacted:url-password]@db/app\n"
+        );
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        assert!(!log.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn background_children_outlive_a_normal_exit() {
+        let pid_file = temp_file("background-pid");
+        let script = format!(
+            "sleep 30 >/dev/null 2>&1 & echo $! > {}",
+            pid_file.display()
+        );
+        let (code, _) = run_command(
+            &sh(&script),
+            &Environment::default(),
+            Path::new("."),
+            StopPolicy::new(None, None),
+            None,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+
+        let pid = read_pid(&pid_file).await;
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(process_alive(pid), "background child {pid} was killed");
+        // SAFETY: kill(2) has no memory-safety preconditions.
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn readers_finish_when_grandchild_holds_pipes() {
+        let stop = StopPolicy::new(None, None);
+        let started = Instant::now();
+        let (code, output) = run_command(
+            &sh("sleep 10 & echo done"),
+            &Environment::default(),
+            Path::new("."),
+            stop,
+            None,
+        )
+        .await
+        .expect("run");
+        assert_eq!(code, Some(0));
+        assert_eq!(output.text().trim(), "done");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,432 @@
+//! What solvers are told: the task wording (built-in or a configured template), source
+//! lines around each annotation, and project instructions files.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::{Check, PromptConfig, PromptTemplate};
+use crate::fix::ErrorGroup;
+use crate::gha::{Annotation, AnnotationLevel};
+use crate::plan::{self, Plan};
+
+/// The task given to solvers when no template is configured.
+pub const DEFAULT_TASK: &str = "\
+Analyze the following build/lint errors and apply fixes directly in the referenced files. \
+Read the files to understand the code context, then edit them to resolve the errors. \
+Be precise and minimal - only change what is necessary to fix the errors.";
+
+/// Added to the built-in task when an analyzer planned the fix.
+const PLANNED_TASK: &str = "\
+Each group comes with the analyzer's plan (root cause, files to change, steps); follow it \
+unless the code shows it is wrong.";
+
+/// Most bytes of instructions files included in one payload; the rest is cut off.
+const MAX_INSTRUCTIONS_BYTES: usize = 32 * 1024;
+
+/// Most bytes of source snippets included in one payload; later annotations get none.
+const MAX_SNIPPET_BYTES: usize = 64 * 1024;
+
+/// Source files larger than this aren't read for snippets.
+const MAX_SOURCE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// A check's prompt settings plus what templates may refer to.
+#[derive(Debug, Clone, Default)]
+pub struct SolverPrompt {
+    /// The check's command line, for `{{command}}`.
+    pub command: String,
+    pub config: PromptConfig,
+    /// The analyzer's plans for this run's groups, by error type, for `{{plan}}`.
+    pub plans: HashMap<String, Plan>,
+}
+
+impl SolverPrompt {
+    pub fn for_check(check: &Check) -> Self {
+        let command = std::iter::once(check.command.program.as_str())
+            .chain(check.command.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Self {
+            command,
+            config: check.prompt.clone(),
+            plans: HashMap::new(),
+        }
+    }
+}
+
+/// A project instructions file included in the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instructions {
+    pub path: String,
+    pub content: String,
+}
+
+/// Everything rendered for one solver run.
+#[derive(Debug, Default)]
+pub struct Rendered {
+    pub task: String,
+    /// Per group, per annotation: the numbered source lines around it, if any.
+    pub snippets: Vec<Vec<Option<String>>>,
+    pub instructions: Vec<Instructions>,
+}
+
+/// Render the prompt for `groups`, reading sources, instructions and the template from
+/// `root`.
+pub fn render(prompt: &SolverPrompt, groups: &[ErrorGroup], root: &Path) -> Result<Rendered> {
+    let mut sources = SourceCache::new(MAX_SNIPPET_BYTES);
+    let snippets: Vec<Vec<Option<String>>> = groups
+        .iter()
+        .map(|group| {
+            group
+                .annotations
+                .iter()
+                .map(|ann| sources.snippet(root, ann, prompt.config.context_lines))
+                .collect()
+        })
+        .collect();
+    let instructions = load_instructions(root, &prompt.config.instructions);
+
+    let task = match &prompt.config.template {
+        None if prompt.plans.is_empty() => DEFAULT_TASK.to_string(),
+        None => format!("{DEFAULT_TASK} {PLANNED_TASK}"),
+        Some(template) => {
+            let template = load_template(template, root)?;
+            let vars = variables(prompt, groups, &snippets, &instructions);
+            substitute(&template, &vars)
+        }
+    };
+    Ok(Rendered {
+        task,
+        snippets,
+        instructions,
+    })
+}
+
+fn load_template(template: &PromptTemplate, root: &Path) -> Result<String> {
+    match template {
+        PromptTemplate::Inline(text) => Ok(text.clone()),
+        PromptTemplate::File(path) => std::fs::read_to_string(root.join(path))
+            .with_context(|| format!("failed to read prompt template {}", path.display())),
+    }
+}
+
+/// Instructions files that exist under `root`; missing ones are skipped so a shared
+/// config can list files only some projects have. Together they're cut off at
+/// [`MAX_INSTRUCTIONS_BYTES`].
+fn load_instructions(root: &Path, paths: &[PathBuf]) -> Vec<Instructions> {
+    let mut budget = MAX_INSTRUCTIONS_BYTES;
+    paths
+        .iter()
+        .filter_map(|path| {
+            let content = read_capped(&under_root(root, path)?, budget)?;
+            budget -= content.len();
+            Some(Instructions {
+                path: path.display().to_string(),
+                content,
+            })
+        })
+        .collect()
+}
+
+/// `file` (relative to `root`, or absolute) with symlinks and `..` resolved, if it exists and
+/// is inside `root`.
+fn under_root(root: &Path, file: &Path) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let path = root.join(file).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
+}
+
+/// A text file's content up to `limit` bytes, cut at the last whole line that fits. `None`
+/// if it can't be read, isn't UTF-8 or nothing fits.
+fn read_capped(path: &Path, limit: usize) -> Option<String> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(limit as u64 + 1)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if bytes.len() > limit {
+        let end = bytes[..limit].iter().rposition(|b| *b == b'\n')? + 1;
+        bytes.truncate(end);
+    }
+    String::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.is_empty())
+}
+
+fn variables(
+    prompt: &SolverPrompt,
+    groups: &[ErrorGroup],
+    snippets: &[Vec<Option<String>>],
+    instructions: &[Instructions],
+) -> HashMap<&'static str, String> {
+    let check = groups.first().map(|g| g.check.clone()).unwrap_or_default();
+
+    let mut files: Vec<&str> = groups
+        .iter()
+        .flat_map(|g| g.files.iter().map(String::as_str))
+        .collect();
+    files.sort_unstable();
+    files.dedup();
+
+    let mut annotations = String::new();
+    let mut snippet_text = String::new();
+    for (group, group_snippets) in groups.iter().zip(snippets) {
+        for (ann, snippet) in group.annotations.iter().zip(group_snippets) {
+            let _ = writeln!(annotations, "{}", describe(ann));
+            if let Some(snippet) = snippet {
+                let _ = writeln!(snippet_text, "{}\n{snippet}", location(ann));
+            }
+        }
+    }
+
+    let mut instruction_text = String::new();
+    for file in instructions {
+        let _ = writeln!(
+            instruction_text,
+            "{}:\n{}",
+            file.path,
+            file.content.trim_end()
+        );
+    }
+
+    HashMap::from([
+        ("check", check),
+        ("command", prompt.command.clone()),
+        ("files", files.join("\n")),
+        ("annotations", annotations.trim_end().to_string()),
+        ("snippets", snippet_text.trim_end().to_string()),
+        ("instructions", instruction_text.trim_end().to_string()),
+        ("plan", plan::describe(&prompt.plans)),
+    ])
+}
+
+/// Replace `{{name}}` placeholders; unknown ones are left as written.
+fn substitute(template: &str, vars: &HashMap<&'static str, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match vars.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn location(ann: &Annotation) -> String {
+    match (&ann.file, ann.line) {
+        (Some(file), Some(line)) => format!("{}:{line}", file.display()),
+        (Some(file), None) => file.display().to_string(),
+        (None, _) => "(no file)".to_string(),
+    }
+}
+
+fn describe(ann: &Annotation) -> String {
+    let level = match ann.level {
+        AnnotationLevel::Error => "error",
+        AnnotationLevel::Warning => "warning",
+        AnnotationLevel::Notice => "notice",
+    };
+    match &ann.title {
+        Some(title) => format!("{}: {level}: {title}: {}", location(ann), ann.message),
+        None => format!("{}: {level}: {}", location(ann), ann.message),
+    }
+}
+
+/// Files read for snippets, so several annotations in one file read it once.
+struct SourceCache {
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+    /// Bytes of snippets still allowed.
+    budget: usize,
+}
+
+impl SourceCache {
+    fn new(budget: usize) -> Self {
+        Self {
+            files: HashMap::new(),
+            budget,
+        }
+    }
+
+    /// The annotated lines with `context` lines around them, numbered, with the annotated
+    /// ones marked `>`.
+    fn snippet(&mut self, root: &Path, ann: &Annotation, context: usize) -> Option<String> {
+        if context == 0 {
+            return None;
+        }
+        let file = ann.file.as_ref()?;
+        let first = usize::try_from(ann.line?).ok()?.max(1);
+        let last = ann
+            .end_line
+            .and_then(|end| usize::try_from(end).ok())
+            .unwrap_or(first)
+            .max(first);
+
+        // Annotations come from tool output; only files inside the root are read.
+        let path = under_root(root, file)?;
+        let lines = self
+            .files
+            .entry(path)
+            .or_insert_with_key(|path| {
+                if std::fs::metadata(path).ok()?.len() > MAX_SOURCE_BYTES {
+                    return None;
+                }
+                let text = std::fs::read_to_string(path).ok()?;
+                Some(text.lines().map(str::to_string).collect())
+            })
+            .as_ref()?;
+        if first > lines.len() {
+            return None;
+        }
+
+        let from = first.saturating_sub(context).max(1);
+        let to = (last + context).min(lines.len());
+        let width = to.to_string().len();
+        let mut out = String::new();
+        for number in from..=to {
+            let marker = if (first..=last).contains(&number) {
+                '>'
+            } else {
+                ' '
+            };
+            let _ = writeln!(out, "{marker}{number:>width$} | {}", lines[number - 1]);
+        }
+        let out = out.trim_end_matches('\n');
+        self.budget = self.budget.checked_sub(out.len())?;
+        Some(out.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gha::parse_annotations;
+
+    fn group(annotations: Vec<Annotation>) -> ErrorGroup {
+        ErrorGroup {
+            check: "lint".to_string(),
+            error_type: "E1".to_string(),
+            files: vec!["src/a.rs".to_string()],
+            annotations,
+        }
+    }
+
+    #[test]
+    fn template_placeholders_are_filled_in() {
+        let root = std::env::temp_dir().join(format!("scanner-rs-prompt-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let source: String = (1..=9).map(|n| format!("line {n}\n")).collect();
+        std::fs::write(root.join("src/a.rs"), source).unwrap();
+        std::fs::write(root.join("AGENTS.md"), "Use tabs.\n").unwrap();
+
+        let annotations =
+            parse_annotations("::error file=src/a.rs,line=5,title=E1::unused variable");
+        let prompt = SolverPrompt {
+            command: "cargo clippy".to_string(),
+            config: PromptConfig {
+                template: Some(PromptTemplate::Inline(
+                    "Fix {{check}} ({{command}}):\n{{annotations}}\n{{snippets}}\n\
+                     {{instructions}}\n{{unknown}}"
+                        .to_string(),
+                )),
+                context_lines: 1,
+                instructions: vec!["AGENTS.md".into(), "MISSING.md".into()],
+            },
+            plans: HashMap::new(),
+        };
+        let rendered = render(&prompt, &[group(annotations)], &root).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            rendered.task,
+            "Fix lint (cargo clippy):\n\
+             src/a.rs:5: error: E1: unused variable\n\
+             src/a.rs:5\n 4 | line 4\n>5 | line 5\n 6 | line 6\n\
+             AGENTS.md:\nUse tabs.\n{{unknown}}"
+        );
+        assert_eq!(rendered.snippets.len(), 1);
+        assert_eq!(
+            rendered.snippets[0][0].as_deref(),
+            Some(" 4 | line 4\n>5 | line 5\n 6 | line 6")
+        );
+        assert_eq!(
+            rendered.instructions,
+            vec![Instructions {
+                path: "AGENTS.md".to_string(),
+                content: "Use tabs.\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn default_prompt_uses_builtin_task_without_snippets() {
+        let annotations = parse_annotations("::error file=src/a.rs,line=5::boom");
+        let rendered = render(
+            &SolverPrompt::default(),
+            &[group(annotations)],
+            Path::new("/nonexistent"),
+        )
+        .unwrap();
+        assert_eq!(rendered.task, DEFAULT_TASK);
+        assert_eq!(rendered.snippets, vec![vec![None]]);
+        assert!(rendered.instructions.is_empty());
+    }
+
+    #[test]
+    fn files_outside_the_root_are_not_read_and_sizes_are_capped() {
+        let base =
+            std::env::temp_dir().join(format!("scanner-rs-prompt-bounds-{}", std::process::id()));
+        let root = base.join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(base.join("secret.txt"), "password\n").unwrap();
+        let long: String = (0..MAX_INSTRUCTIONS_BYTES / 10 + 5)
+            .map(|n| format!("rule {n:04}\n"))
+            .collect();
+        std::fs::write(root.join("AGENTS.md"), &long).unwrap();
+        std::fs::write(root.join("CLAUDE.md"), "Use tabs.\n").unwrap();
+
+        let outside = base.join("secret.txt").display().to_string();
+        let annotations = parse_annotations(&format!(
+            "::error file=../secret.txt,line=1::boom\n::error file={outside},line=1::boom"
+        ));
+        let prompt = SolverPrompt {
+            config: PromptConfig {
+                context_lines: 1,
+                instructions: vec![
+                    "AGENTS.md".into(),
+                    "CLAUDE.md".into(),
+                    "../secret.txt".into(),
+                ],
+                ..PromptConfig::default()
+            },
+            ..SolverPrompt::default()
+        };
+        let rendered = render(&prompt, &[group(annotations)], &root).unwrap();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(rendered.snippets, vec![vec![None, None]]);
+        // The first file uses up the budget (cut at a line end); the others don't fit.
+        assert_eq!(rendered.instructions.len(), 1);
+        let content = &rendered.instructions[0].content;
+        assert!(content.len() <= MAX_INSTRUCTIONS_BYTES && content.ends_with('\n'));
+        assert!(long.starts_with(content.as_str()));
+    }
+}
@@ -0,0 +1,146 @@
+//! Pseudo-terminals for checks that only behave (or run at all) with a TTY.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll, ready};
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::process::Command;
+
+use crate::config::PtySize;
+
+/// A freshly opened pseudo-terminal pair.
+pub struct Pty {
+    master: OwnedFd,
+    slave: OwnedFd,
+}
+
+impl Pty {
+    pub fn open(size: PtySize) -> io::Result<Self> {
+        let mut master: RawFd = -1;
+        let mut slave: RawFd = -1;
+        let mut winsize = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: the out-pointers are valid for writes; name and termios may be null.
+        let rc = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &raw mut winsize,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: openpty succeeded, so both descriptors are open and owned by us.
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        // Other checks spawn concurrently; a leaked slave would keep this terminal open.
+        set_cloexec(&master)?;
+        set_cloexec(&slave)?;
+        set_nonblocking(&master)?;
+        Ok(Self { master, slave })
+    }
+
+    /// Make the terminal the command's stdout, stderr and (unless `piped_stdin`) stdin,
+    /// and its controlling terminal in a new session, which also gives the command its
+    /// own process group. Returns the reader for the merged output.
+    ///
+    /// The command must be dropped once spawned so the parent's copies of the slave close
+    /// and the reader sees EOF when the command exits.
+    pub fn attach(self, cmd: &mut Command, piped_stdin: bool) -> io::Result<PtyReader> {
+        if !piped_stdin {
+            cmd.stdin(Stdio::from(self.slave.try_clone()?));
+        }
+        cmd.stdout(Stdio::from(self.slave.try_clone()?));
+        cmd.stderr(Stdio::from(self.slave));
+        // SAFETY: only async-signal-safe calls (setsid, ioctl) run between fork and exec.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Best-effort: stdout is the slave; without a controlling terminal some
+                // tools still see a TTY through isatty().
+                libc::ioctl(libc::STDOUT_FILENO, libc::TIOCSCTTY as _, 0);
+                Ok(())
+            });
+        }
+        Ok(PtyReader {
+            fd: AsyncFd::new(self.master)?,
+        })
+    }
+}
+
+/// Reads the output written to a pseudo-terminal.
+pub struct PtyReader {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl AsyncRead for PtyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            let res = guard.try_io(|fd| {
+                // SAFETY: `unfilled` is valid for writes of its length.
+                let n = unsafe {
+                    libc::read(fd.as_raw_fd(), unfilled.as_mut_ptr().cast(), unfilled.len())
+                };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            match res {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                // Linux reports EIO once every slave descriptor is closed: that's EOF.
+                Ok(Err(e)) if e.raw_os_error() == Some(libc::EIO) => return Poll::Ready(Ok(())),
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+fn set_cloexec(fd: &OwnedFd) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor we own.
+    let rc = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_nonblocking(fd: &OwnedFd) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor we own.
+    let rc = unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+        if flags < 0 {
+            flags
+        } else {
+            libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK)
+        }
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Backing off when an agent's provider turns runs away with a rate limit (HTTP 429 or an
+//! "overloaded" error), shared by all solvers talking to the same provider.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use regex::Regex;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Agent;
+
+/// Longest wait between retries, however often the provider said no.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Structured provider errors: Anthropic's `rate_limit_error` and `overloaded_error` types,
+/// OpenAI's `rate_limit_exceeded` code, or a 429 status field.
+static STRUCTURED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)"(?:type|code)"\s*:\s*"(?:rate_limit_error|overloaded_error|rate_limit_exceeded)"|"status(?:_code)?"\s*:\s*429\b"#)
+        .expect("structured pattern is valid")
+});
+
+/// A line reporting an error.
+static ERROR_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\berror\b").expect("error line pattern is valid"));
+
+/// What an error line says when the provider turned the request away.
+static RATE_LIMIT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\bstatus:?\s*429\b|\b429 too many requests\b|\brate limit (?:exceeded|reached)\b",
+    )
+    .expect("rate limit pattern is valid")
+});
+
+/// A wait the provider asked for: "try again in 20s", "retry after 3 seconds",
+/// "Retry-After: 20".
+static RETRY_AFTER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:try again in|retry[ -]after:?)\s*(\d+(?:\.\d+)?)\s*(ms|s|sec|seconds?)?\b")
+        .expect("retry-after pattern is valid")
+});
+
+/// Whether an agent's error output says its provider rate limited it: a structured provider
+/// error, or an error line with a 429 status or a rate limit message. Anything else that
+/// mentions 429 or rate limits (a `foo.rs:429` location, a `rate_limit` identifier) doesn't
+/// count.
+pub fn is_rate_limited(text: &str) -> bool {
+    text.lines().any(|line| {
+        STRUCTURED.is_match(line) || (ERROR_LINE.is_match(line) && RATE_LIMIT.is_match(line))
+    })
+}
+
+/// The wait the provider asked for, if it said.
+pub fn retry_after(text: &str) -> Option<Duration> {
+    let caps = RETRY_AFTER.captures(text)?;
+    let amount: f64 = caps[1].parse().ok()?;
+    let seconds = match caps.get(2).map(|unit| unit.as_str()) {
+        Some(unit) if unit.eq_ignore_ascii_case("ms") => amount / 1000.0,
+        _ => amount,
+    };
+    Some(Duration::from_secs_f64(seconds).min(MAX_BACKOFF))
+}
+
+/// The provider an agent talks to, as far as rate limits go: the name of its program.
+pub fn provider(agent: &Agent) -> String {
+    Path::new(&agent.command.program)
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The wait before retry number `attempt` (from 1): the provider's own, or `base` doubled
+/// for each earlier retry.
+pub fn backoff(base: Duration, attempt: u32, asked: Option<Duration>) -> Duration {
+    asked
+        .unwrap_or_else(|| base.saturating_mul(1 << attempt.saturating_sub(1).min(16)))
+        .min(MAX_BACKOFF)
+}
+
+/// When each provider will take requests again. Cloning shares the state.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimits(Arc<Mutex<HashMap<String, Instant>>>);
+
+impl RateLimits {
+    /// Hold off new runs against `provider` for `wait` (never shortening a longer pause).
+    pub fn pause(&self, provider: &str, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut paused = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = paused.entry(provider.to_string()).or_insert(until);
+        *entry = (*entry).max(until);
+    }
+
+    /// Wait until `provider` takes requests again (or the run is cancelled).
+    pub async fn ready(&self, provider: &str, cancel: &CancellationToken) {
+        loop {
+            let until = {
+                let paused = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                paused.get(provider).copied()
+            };
+            match until {
+                Some(until) if until > Instant::now() => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(until) => {}
+                        _ = cancel.cancelled() => return,
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_provider_rate_limits() {
+        assert!(is_rate_limited(
+            "ERROR: exceeded retry limit, last status: 429 Too Many Requests"
+        ));
+        assert!(is_rate_limited(
+            r#"API Error: {"type":"error","error":{"type":"rate_limit_error"}}"#
+        ));
+        assert!(is_rate_limited(r#"{"type":"overloaded_error"}"#));
+        assert!(is_rate_limited(
+            r#"{"error":{"code":"rate_limit_exceeded","message":"Rate limit reached"}}"#
+        ));
+        assert!(is_rate_limited("error: Rate limit exceeded for model"));
+        assert!(!is_rate_limited(
+            "error: could not compile `scanner` (4290 errors)"
+        ));
+        // Tool output passed along in the agent's error doesn't count.
+        assert!(!is_rate_limited(
+            "src/foo.rs:429:5: error: mismatched types"
+        ));
+        assert!(!is_rate_limited(
+            "error[E0425]: cannot find value `rate_limit` in this scope"
+        ));
+        assert!(!is_rate_limited(
+            "warning: build server overloaded, retrying"
+        ));
+        assert!(!is_rate_limited(
+            "Too many requests to the cache; rate limit 429 per hour"
+        ));
+
+        assert_eq!(
+            retry_after("Rate limit reached. Please try again in 1.5s."),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            retry_after("retry-after: 20"),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            retry_after("try again in 800ms"),
+            Some(Duration::from_millis(800))
+        );
+        assert_eq!(retry_after("429 Too Many Requests"), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        let base = Duration::from_secs(20);
+        assert_eq!(backoff(base, 1, None), Duration::from_secs(20));
+        assert_eq!(backoff(base, 3, None), Duration::from_secs(80));
+        assert_eq!(backoff(base, 10, None), MAX_BACKOFF);
+        let asked = Some(Duration::from_secs(5));
+        assert_eq!(backoff(base, 3, asked), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn pauses_hold_back_runs_for_the_same_provider() {
+        let limits = RateLimits::default();
+        limits.pause("codex", Duration::from_millis(100));
+        limits.pause("codex", Duration::from_millis(10));
+
+        let cancel = CancellationToken::new();
+        let started = Instant::now();
+        limits.ready("claude", &cancel).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+        limits.ready("codex", &cancel).await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,294 @@
+//! `--review`: solvers work in scratch worktrees, and their changes are offered hunk by hunk
+//! instead of landing in the working tree unsupervised.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use tokio::sync::oneshot;
+
+use crate::snapshot;
+
+/// What the developer made of a hunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Decision {
+    #[default]
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+/// One hunk of a file's diff, starting with its `@@` line. Changes without hunks (binary
+/// files, mode changes) are a single hunk with no lines and are taken or left as a whole.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub lines: Vec<String>,
+    pub decision: Decision,
+}
+
+/// The diff of one file: its `diff --git` header lines and hunks.
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    pub path: String,
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// What to show for a hunk: its lines, or the file header for whole-file changes.
+    pub fn display_lines<'a>(&'a self, hunk: &'a Hunk) -> &'a [String] {
+        if hunk.lines.is_empty() {
+            &self.header
+        } else {
+            &hunk.lines
+        }
+    }
+}
+
+/// The changes one solver proposed for a check's errors.
+#[derive(Debug, Clone)]
+pub struct ProposedFix {
+    pub check: String,
+    pub files: Vec<FilePatch>,
+}
+
+impl ProposedFix {
+    /// Split a `git diff` patch into files and hunks.
+    pub fn parse(check: &str, patch: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(patch);
+        let mut files: Vec<FilePatch> = Vec::new();
+        for line in text.lines() {
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                let path = paths
+                    .rsplit_once(" b/")
+                    .map(|(_, path)| path)
+                    .unwrap_or(paths)
+                    .to_string();
+                files.push(FilePatch {
+                    path,
+                    header: vec![line.to_string()],
+                    hunks: Vec::new(),
+                });
+                continue;
+            }
+            let Some(file) = files.last_mut() else {
+                continue;
+            };
+            if line.starts_with("@@") {
+                file.hunks.push(Hunk {
+                    lines: vec![line.to_string()],
+                    decision: Decision::Pending,
+                });
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                hunk.lines.push(line.to_string());
+            } else {
+                file.header.push(line.to_string());
+            }
+        }
+        for file in &mut files {
+            if file.hunks.is_empty() {
+                file.hunks.push(Hunk {
+                    lines: Vec::new(),
+                    decision: Decision::Pending,
+                });
+            }
+        }
+        Self {
+            check: check.to_string(),
+            files,
+        }
+    }
+
+    /// The patch text, with every hunk or only the accepted ones.
+    pub fn to_patch(&self, only_accepted: bool) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let hunks: Vec<&Hunk> = file
+                .hunks
+                .iter()
+                .filter(|hunk| !only_accepted || hunk.decision == Decision::Accepted)
+                .collect();
+            if hunks.is_empty() {
+                continue;
+            }
+            for line in file
+                .header
+                .iter()
+                .chain(hunks.iter().flat_map(|h| &h.lines))
+            {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Apply the accepted hunks to the repository containing `root`; `false` if none were
+    /// accepted. Nothing is changed if any of them fails to apply.
+    pub fn apply(&self, root: &Path) -> Result<bool> {
+        let patch = self.to_patch(true);
+        if patch.is_empty() {
+            return Ok(false);
+        }
+        snapshot::apply_to(root, patch.as_bytes())?;
+        Ok(true)
+    }
+}
+
+/// Where a hunk sits in a list of fixes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HunkRef {
+    pub fix: usize,
+    pub file: usize,
+    pub hunk: usize,
+}
+
+/// Every hunk of `fixes`, in order.
+pub fn hunk_refs(fixes: &[ProposedFix]) -> Vec<HunkRef> {
+    let mut refs = Vec::new();
+    for (fix_idx, fix) in fixes.iter().enumerate() {
+        for (file_idx, file) in fix.files.iter().enumerate() {
+            for hunk_idx in 0..file.hunks.len() {
+                refs.push(HunkRef {
+                    fix: fix_idx,
+                    file: file_idx,
+                    hunk: hunk_idx,
+                });
+            }
+        }
+    }
+    refs
+}
+
+/// Open a hunk in the developer's editor (`$VISUAL`, `$EDITOR`, or `vi`). An edited hunk
+/// counts as accepted; line counts are recomputed when it is applied.
+pub fn edit_hunk(file_path: &str, hunk: &mut Hunk) -> Result<()> {
+    if hunk.lines.is_empty() {
+        anyhow::bail!("{file_path}: binary and mode changes can't be edited");
+    }
+    // Created exclusively, readable by the developer only, under a name nobody can guess.
+    let mut file = tempfile::Builder::new()
+        .prefix("scanner-hunk-")
+        .suffix(&format!("-{}.diff", file_path.replace(['/', '\\'], "_")))
+        .tempfile()?;
+    let mut text = hunk.lines.join("\n");
+    text.push('\n');
+    file.write_all(text.as_bytes())?;
+    file.flush()?;
+    let path = file.path().to_path_buf();
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor setting may carry arguments (`code --wait`).
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("failed to start editor '{editor}'"))?;
+    // Editors may replace the file rather than write to it, so read it back by name.
+    let edited = std::fs::read_to_string(&path);
+    drop(file);
+    if !status.success() {
+        anyhow::bail!("editor '{editor}' exited with {status}");
+    }
+    hunk.lines = edited?.lines().map(str::to_string).collect();
+    hunk.decision = Decision::Accepted;
+    Ok(())
+}
+
+/// Write each fix to `<dir>/<check>.patch` for review outside scanner.
+pub fn write_patches(dir: &Path, fixes: &[ProposedFix]) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut written = Vec::new();
+    for fix in fixes {
+        let name: String = fix
+            .check
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = dir.join(format!("{name}.patch"));
+        std::fs::write(&path, fix.to_patch(false))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Hands the reviewed fixes back from the UI. Dropping it without replying rejects them all.
+#[derive(Debug, Clone)]
+pub struct ReviewReply(Arc<Mutex<Option<oneshot::Sender<Vec<ProposedFix>>>>>);
+
+impl ReviewReply {
+    pub fn channel() -> (Self, oneshot::Receiver<Vec<ProposedFix>>) {
+        let (tx, rx) = oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(tx)))), rx)
+    }
+
+    pub fn send(&self, fixes: Vec<ProposedFix>) {
+        let sender = self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(sender) = sender {
+            let _ = sender.send(fixes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+-let a = 1;
++let a = 2;
+ let b = 2;
+ let c = 3;
+@@ -10,2 +10,2 @@
+ let x = 1;
+-let y = 1;
++let y = 2;
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..3333333
+Binary files /dev/null and b/logo.png differ
+";
+
+    #[test]
+    fn patches_split_into_files_and_hunks() {
+        let fix = ProposedFix::parse("lint", PATCH.as_bytes());
+        assert_eq!(fix.files.len(), 2);
+        assert_eq!(fix.files[0].path, "src/lib.rs");
+        assert_eq!(fix.files[0].header.len(), 4);
+        assert_eq!(fix.files[0].hunks.len(), 2);
+        assert_eq!(fix.files[1].path, "logo.png");
+        assert_eq!(hunk_refs(std::slice::from_ref(&fix)).len(), 3);
+        assert_eq!(fix.to_patch(false), PATCH);
+    }
+
+    #[test]
+    fn only_accepted_hunks_are_kept() {
+        let mut fix = ProposedFix::parse("lint", PATCH.as_bytes());
+        assert_eq!(fix.to_patch(true), "");
+
+        fix.files[0].hunks[1].decision = Decision::Accepted;
+        fix.files[1].hunks[0].decision = Decision::Rejected;
+        let patch = fix.to_patch(true);
+        assert!(patch.starts_with("diff --git a/src/lib.rs b/src/lib.rs\n"));
+        assert!(patch.contains("+let y = 2;"));
+        assert!(!patch.contains("+let a = 2;"));
+        assert!(!patch.contains("logo.png"));
+    }
+}
@@ -1,13 +1,19 @@
+use std::collections::HashSet;
 use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
-use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
 
-use crate::config::Check;
-use crate::error::CheckError;
-use crate::gha::{Annotation, AnnotationLevel, is_error_level, parse_annotations};
-use crate::ui::UiEvent;
+use crate::config::{Check, RetryCondition};
+use crate::error::{CheckError, ProcessError};
+use crate::gha::{
+    Annotation, AnnotationLevel, is_error_level, parse_annotation_line, parse_annotations,
+};
+use crate::process::{CommandOutput, OutputSink, StopPolicy};
+use crate::ui::{StreamType, UiEvent};
 
+use super::CheckContext;
 use super::process_runner::{run_formatter, run_process, run_process_streaming};
 
 #[derive(Debug, Clone)]
@@ -16,36 +22,167 @@ pub struct CheckResult {
     pub exit_code: Option<i32>,
     pub raw_output: String,
     pub annotations: Vec<Annotation>,
+    /// The check was stopped before finishing because the run was cancelled.
+    pub cancelled: bool,
+    /// Number of times the check command ran (more than one when retried).
+    pub attempts: u32,
+    /// The check failed at first but passed on a retry.
+    pub flaky: bool,
+    /// Wall time spent running the check (all attempts, fixer and rerun included).
+    pub duration: Duration,
+    /// File holding the full output of the last run, when logs are enabled.
+    pub log_path: Option<PathBuf>,
+}
+
+impl CheckResult {
+    /// The check exited with a success code and produced no error annotations (output
+    /// assertions included).
+    pub fn passed(&self) -> bool {
+        self.check.exit_succeeded(self.exit_code)
+            && !self.annotations.iter().any(|a| is_error_level(a.level))
+    }
+
+    /// The check failed in a way that fails the run: not cancelled, and not advisory.
+    pub fn fails_run(&self) -> bool {
+        !self.cancelled && !self.passed() && !self.check.is_advisory()
+    }
+
+    /// The tool itself failed (an `error_exit_codes` exit); nothing a solver can fix.
+    pub fn tool_failed(&self) -> bool {
+        self.check.tool_failed(self.exit_code)
+    }
+
+    /// This run of a check went worse than `before`: it fails where it used to pass, or
+    /// reports errors `before` didn't (wherever in their file they moved). Cancelled runs
+    /// prove nothing either way.
+    pub fn is_worse_than(&self, before: &CheckResult) -> bool {
+        if self.cancelled || before.cancelled {
+            return false;
+        }
+        if before.passed() {
+            return !self.passed();
+        }
+        let known = before.error_fingerprints();
+        self.error_fingerprints()
+            .iter()
+            .any(|error| !known.contains(error))
+    }
+
+    fn error_fingerprints(&self) -> HashSet<(Option<&PathBuf>, Option<&str>, &str)> {
+        self.annotations
+            .iter()
+            .filter(|a| is_error_level(a.level))
+            .map(|a| (a.file.as_ref(), a.title.as_deref(), a.message.as_str()))
+            .collect()
+    }
 }
 
 pub(crate) async fn run_single_check(
     check: &Check,
-    root: &Path,
-    ui_tx: Option<Sender<UiEvent>>,
+    ctx: &CheckContext,
+    cancel: &CancellationToken,
 ) -> Result<CheckResult> {
-    let initial = run_check_once(check, root, ui_tx.clone()).await?;
+    let root = ctx.root.as_path();
+    let initial = run_with_retries(check, ctx, cancel, &check.name).await?;
 
-    if initial.exit_code == Some(0) && !initial.annotations.iter().any(|a| is_error_level(a.level))
-    {
+    // A crashed or missing tool isn't something the fixer can fix.
+    if initial.passed() || initial.tool_failed() {
         return Ok(initial);
     }
 
     if let Some(fixer_cmd) = &check.fixer {
         let _ = run_process(
             fixer_cmd,
-            &check.env,
-            check.timeout,
+            &check.environment(),
+            stop_policy(check, cancel),
             root,
             check.cwd.as_ref(),
         )
         .await;
-        let rerun = run_check_once(check, root, ui_tx).await?;
-        return Ok(rerun);
+        if cancel.is_cancelled() {
+            return Err(ProcessError::Cancelled.into());
+        }
+        let log_name = format!("{}.after-fixer", check.name);
+        return run_with_retries(check, ctx, cancel, &log_name).await;
     }
 
     Ok(initial)
 }
 
+/// Run the check, retrying qualifying failures up to `check.retries` times. Logs are named
+/// after `log_name`.
+async fn run_with_retries(
+    check: &Check,
+    ctx: &CheckContext,
+    cancel: &CancellationToken,
+    log_name: &str,
+) -> Result<CheckResult> {
+    let max_attempts = check.retries + 1;
+    let mut attempt = 1;
+    loop {
+        // Each attempt keeps its own log.
+        let log_name = match attempt {
+            1 => log_name.to_string(),
+            n => format!("{log_name}.attempt-{n}"),
+        };
+        let outcome = run_check_once(check, ctx, cancel, &log_name).await;
+
+        if attempt < max_attempts && !cancel.is_cancelled() && should_retry(check, &outcome) {
+            if let Some(tx) = ctx.ui_tx.as_ref() {
+                let _ = tx
+                    .send(UiEvent::StreamLine {
+                        source: check.name.clone(),
+                        stream: StreamType::Stderr,
+                        line: format!("attempt {attempt}/{max_attempts} failed; retrying"),
+                    })
+                    .await;
+            }
+            attempt += 1;
+            continue;
+        }
+
+        return outcome.map(|(mut result, _)| {
+            result.attempts = attempt;
+            result.flaky = attempt > 1 && result.passed();
+            result
+        });
+    }
+}
+
+/// Whether a failed attempt qualifies for a retry under the check's `retry_on` policy.
+fn should_retry(check: &Check, outcome: &Result<(CheckResult, CommandOutput)>) -> bool {
+    let (result, output) = match outcome {
+        Ok((result, _)) if result.passed() => return false,
+        Ok((result, output)) => (result, output),
+        Err(err) => {
+            let timed_out = matches!(
+                err.downcast_ref::<ProcessError>(),
+                Some(ProcessError::Timeout(_))
+            );
+            // Only timeouts are worth retrying; spawn/formatter errors won't fix themselves.
+            return timed_out
+                && (check.retry_on.is_empty()
+                    || check
+                        .retry_on
+                        .iter()
+                        .any(|c| matches!(c, RetryCondition::Timeout)));
+        }
+    };
+
+    if check.retry_on.is_empty() {
+        return true;
+    }
+    check.retry_on.iter().any(|condition| match condition {
+        RetryCondition::Timeout => false,
+        RetryCondition::ExitCode(code) => result.exit_code == Some(*code),
+        RetryCondition::Output(re) => output.lines().any(|line| re.is_match(&line)),
+    })
+}
+
+fn stop_policy(check: &Check, cancel: &CancellationToken) -> StopPolicy {
+    StopPolicy::new(check.timeout, check.kill_grace).with_cancel(cancel.clone())
+}
+
 fn clean_path(path: &Path) -> PathBuf {
     let mut out = PathBuf::new();
     for comp in path.components() {
@@ -116,31 +253,103 @@ fn normalize_annotation_paths(
     }
 }
 
+/// One error annotation per `fail_if_output_matches` pattern found in the output.
+fn output_assertion_failures(check: &Check, output: &CommandOutput) -> Vec<Annotation> {
+    if check.fail_if_output_matches.is_empty() {
+        return Vec::new();
+    }
+    // The first line each pattern matches, in one pass over the output.
+    let mut found: Vec<Option<String>> = vec![None; check.fail_if_output_matches.len()];
+    for line in output.lines() {
+        for (re, first) in check.fail_if_output_matches.iter().zip(&mut found) {
+            if first.is_none() && re.is_match(&line) {
+                *first = Some(line.clone());
+            }
+        }
+        if found.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    check
+        .fail_if_output_matches
+        .iter()
+        .zip(found)
+        .filter_map(|(re, line)| {
+            let line = line?;
+            Some(Annotation {
+                level: AnnotationLevel::Error,
+                actionable: false,
+                file: None,
+                line: None,
+                end_line: None,
+                column: None,
+                end_column: None,
+                title: Some("output assertion".to_string()),
+                message: format!("output matches `{}`: {}", re.as_str(), line.trim()),
+            })
+        })
+        .collect()
+}
+
+/// Run the check once, logging its output under `log_name`. Returns the result along with
+/// the complete output, which may be longer than the capped `raw_output`.
 async fn run_check_once(
     check: &Check,
-    root: &Path,
-    ui_tx: Option<Sender<UiEvent>>,
-) -> Result<CheckResult> {
-    let (exit_code, combined_output) = run_process_streaming(
+    ctx: &CheckContext,
+    cancel: &CancellationToken,
+    log_name: &str,
+) -> Result<(CheckResult, CommandOutput)> {
+    let root = ctx.root.as_path();
+    let log_path = ctx.logs.as_ref().map(|logs| logs.path_for(log_name));
+    let output = OutputSink {
+        stream: ctx.ui_tx.clone().map(|tx| (check.name.clone(), tx)),
+        max_bytes: Some(ctx.max_output_bytes),
+        log_file: log_path.clone(),
+        pty: check.pty,
+        redactor: Some(ctx.redactor.clone()),
+    };
+    let (exit_code, output) = run_process_streaming(
         &check.command,
-        &check.env,
-        check.timeout,
+        &check.environment(),
+        stop_policy(check, cancel),
         root,
         check.cwd.as_ref(),
-        Some(check.name.clone()),
-        ui_tx,
+        output,
     )
     .await?;
+    // Findings in the middle of a huge output count too; only what is kept is capped.
+    let full_annotations = || -> Vec<Annotation> {
+        output
+            .lines()
+            .filter_map(|line| parse_annotation_line(line.trim_end()))
+            .collect()
+    };
 
-    let (_formatted_output, mut annotations) = if let Some(formatter) = &check.formatter {
-        if exit_code == Some(0) {
+    let (_formatted_output, mut annotations) = if check.tool_failed(exit_code) {
+        // The output of a crashed tool isn't findings; don't parse or format it.
+        let annotation = Annotation {
+            level: AnnotationLevel::Error,
+            actionable: false,
+            file: None,
+            line: None,
+            end_line: None,
+            column: None,
+            end_column: None,
+            title: Some("tool error".to_string()),
+            message: format!(
+                "check exited with {exit_code:?}, which is listed in error_exit_codes; the tool itself failed"
+            ),
+        };
+        (String::new(), vec![annotation])
+    } else if let Some(formatter) = &check.formatter {
+        if check.exit_succeeded(exit_code) {
             (String::new(), Vec::new())
         } else {
             let (fmt_exit, fmt_output) = run_formatter(
                 formatter,
-                &check.env,
-                &combined_output,
-                check.timeout,
+                &check.environment(),
+                output.full_input(),
+                stop_policy(check, cancel),
                 root,
                 check.cwd.as_ref(),
             )
@@ -155,7 +364,7 @@ async fn run_check_once(
             let mut annotations = parse_annotations(&fmt_output);
             if annotations.is_empty() {
                 // Formatter produced no annotations; fall back to parsing the raw output.
-                annotations = parse_annotations(&combined_output);
+                annotations = full_annotations();
             }
             if annotations.is_empty() {
                 annotations.push(Annotation {
@@ -175,8 +384,8 @@ async fn run_check_once(
             (fmt_output, annotations)
         }
     } else {
-        let mut annotations = parse_annotations(&combined_output);
-        if exit_code != Some(0) && annotations.is_empty() {
+        let mut annotations = full_annotations();
+        if !check.exit_succeeded(exit_code) && annotations.is_empty() {
             annotations.push(Annotation {
                 level: AnnotationLevel::Error,
                 actionable: false,
@@ -191,15 +400,26 @@ async fn run_check_once(
                 ),
             });
         }
-        (combined_output.clone(), annotations)
+        (String::new(), annotations)
     };
 
     normalize_annotation_paths(&mut annotations, root, check.cwd.as_ref());
+    annotations.extend(output_assertion_failures(check, &output));
+    // Formatters see the redacted output, but may still echo or decode a secret.
+    for annotation in &mut annotations {
+        ctx.redactor.redact_annotation(annotation);
+    }
 
-    Ok(CheckResult {
+    let result = CheckResult {
         check: check.clone(),
         exit_code,
-        raw_output: combined_output,
+        raw_output: output.text(),
         annotations,
-    })
+        cancelled: false,
+        attempts: 1,
+        flaky: false,
+        duration: Duration::ZERO,
+        log_path: log_path.filter(|path| path.exists()),
+    };
+    Ok((result, output))
 }
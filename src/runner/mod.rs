@@ -1,12 +1,22 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use tokio::sync::Semaphore;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-use crate::config::{Check, Config, Setup};
-use crate::gha::{Annotation, AnnotationLevel, is_error_level};
-use crate::pool::Pool;
+use crate::config::{Check, Config, DEFAULT_MAX_OUTPUT_BYTES, Setup};
+use crate::error::ProcessError;
+use crate::gha::{Annotation, AnnotationLevel};
+use crate::history::HistoryStore;
+use crate::logs::RunLogs;
+use crate::pool::{Demand, Pool, QueueTicket};
+use crate::process::{OutputSink, StopPolicy};
+use crate::redact::Redactor;
 use crate::ui::{UiEvent, sanitize_text_for_tui};
 
 mod execution;
@@ -14,21 +24,30 @@ mod process_runner;
 mod selection;
 
 pub use execution::CheckResult;
+pub(crate) use selection::select_checks;
 
 /// Run a setup command. Returns the exit code.
 pub async fn run_setup(
     setup: &Setup,
     root: &std::path::Path,
+    cancel: &CancellationToken,
     ui_tx: Option<Sender<UiEvent>>,
+    redactor: Arc<Redactor>,
 ) -> Option<i32> {
+    let output = OutputSink {
+        stream: ui_tx.map(|tx| (format!("setup:{}", setup.name), tx)),
+        max_bytes: Some(DEFAULT_MAX_OUTPUT_BYTES),
+        log_file: None,
+        pty: None,
+        redactor: Some(redactor),
+    };
     let result = process_runner::run_process_streaming(
         &setup.command,
-        &setup.env,
-        setup.timeout,
+        &setup.env_policy.resolve(&setup.env),
+        StopPolicy::new(setup.timeout, setup.kill_grace).with_cancel(cancel.clone()),
         root,
         setup.cwd.as_ref(),
-        Some(format!("setup:{}", setup.name)),
-        ui_tx,
+        output,
     )
     .await;
 
@@ -57,124 +76,209 @@ fn synthesize_failed_result(check: Check, error: &str) -> CheckResult {
             title: Some("execution failed".to_string()),
             message: error.to_string(),
         }],
+        cancelled: false,
+        attempts: 1,
+        flaky: false,
+        duration: Duration::ZERO,
+        log_path: None,
+    }
+}
+
+/// Result for a check that was stopped (or never started) because the run was cancelled.
+fn cancelled_result(check: Check) -> CheckResult {
+    CheckResult {
+        check,
+        exit_code: None,
+        raw_output: String::new(),
+        annotations: Vec::new(),
+        cancelled: true,
+        attempts: 1,
+        flaky: false,
+        duration: Duration::ZERO,
+        log_path: None,
+    }
+}
+
+/// How a check phase should run.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Include disabled checks when named explicitly.
+    pub force: bool,
+    /// Suppress plain-text progress when there is no UI.
+    pub quiet: bool,
+    /// Cancel outstanding checks as soon as any check fails.
+    pub fail_fast: bool,
+    /// Never stop early, even when a `critical` check fails.
+    pub keep_going: bool,
+    /// Where history and logs live when that isn't the check root (`--snapshot`).
+    pub state_root: Option<PathBuf>,
+}
+
+/// Settings shared by every check of a run.
+#[derive(Debug, Clone)]
+pub struct CheckContext {
+    pub root: PathBuf,
+    /// Suppress plain-text progress when there is no UI.
+    pub quiet: bool,
+    pub ui_tx: Option<Sender<UiEvent>>,
+    /// In-memory cap for each output stream of a check.
+    pub max_output_bytes: usize,
+    /// Where the full output of each check is written, if anywhere.
+    pub logs: Option<RunLogs>,
+    /// Masks secrets in output and annotations.
+    pub redactor: Arc<Redactor>,
+}
+
+impl RunOptions {
+    /// Whether a failure of `check` should stop the rest of the run.
+    fn stops_on_failure(&self, check: &Check) -> bool {
+        !self.keep_going && !check.is_advisory() && (self.fail_fast || check.critical)
+    }
+
+    /// The failed check that stopped the run early (`--fail-fast` or a `critical` check),
+    /// if one did.
+    pub fn stopped_by<'a>(&self, results: &'a [CheckResult]) -> Option<&'a CheckResult> {
+        results
+            .iter()
+            .find(|res| !res.cancelled && !res.passed() && self.stops_on_failure(&res.check))
     }
 }
 
 pub async fn run_checks(
     config: &Config,
     filters: &[String],
-    force: bool,
+    options: RunOptions,
     pool: &Pool,
-    quiet: bool,
     ui_events: Option<Sender<UiEvent>>,
     root: &std::path::Path,
 ) -> Vec<CheckResult> {
-    let selected = selection::select_checks(config, filters, force);
+    let selected = selection::select_checks(config, filters, options.force);
 
     if selected.is_empty() {
         return Vec::new();
     }
 
-    // Optional per-check lock groups to serialize contended tools/resources.
-    let mut lock_groups: HashMap<String, Arc<Semaphore>> = HashMap::new();
-    for check in &selected {
-        if let Some(lock) = check.lock.as_ref() {
-            lock_groups
-                .entry(lock.clone())
-                .or_insert_with(|| Arc::new(Semaphore::new(1)));
-        }
+    let state_root = options.state_root.as_deref().unwrap_or(root);
+    let history = HistoryStore::load(state_root);
+    let expected: HashMap<String, Duration> = selected
+        .iter()
+        .filter_map(|check| {
+            history
+                .expected_duration(&check.name)
+                .map(|d| (check.name.clone(), d))
+        })
+        .collect();
+
+    if let Some(tx) = ui_events.as_ref()
+        && let Some(estimate) = estimate_run(&selected, &expected, pool.capacity())
+    {
+        let _ = tx.send(UiEvent::RunEstimate { expected: estimate }).await;
     }
-    let lock_groups = Arc::new(lock_groups);
-
-    let mut handles = Vec::new();
-
-    for check in selected {
-        let check_clone = check.clone();
-        let check_for_join = check.clone();
-        let ui_tx = ui_events.clone();
-        let root = root.to_path_buf();
-        let lock_groups = lock_groups.clone();
-
-        // Spawn through the pool - waits for a slot if pool is full
-        let handle = pool.spawn(async move {
-            let _lock_permit = match check_clone.lock.as_deref() {
-                Some(lock) => lock_groups
-                    .get(lock)
-                    .expect("lock group present")
-                    .clone()
-                    .acquire_owned()
-                    .await
-                    .ok(),
-                None => None,
-            };
 
-            if let Some(tx) = ui_tx.as_ref() {
-                let _ = tx
-                    .send(UiEvent::CheckStarted {
-                        name: check_clone.name.clone(),
-                        desc: check_clone.description.clone(),
-                    })
-                    .await;
-            } else if !quiet {
-                eprintln!("running check: {}", check_clone.name);
+    let ctx = CheckContext {
+        root: root.to_path_buf(),
+        quiet: options.quiet,
+        ui_tx: ui_events.clone(),
+        max_output_bytes: config.output.max_bytes,
+        logs: RunLogs::create(state_root, config.output.keep_logs),
+        redactor: Arc::new(Redactor::new(&config.redaction)),
+    };
+    let lock_groups = lock_groups(&selected, &config.locks);
+    // A child of the pool token, so fail-fast can stop this run without cancelling the pool.
+    let cancel = pool.cancel_token().child_token();
+    let stopped_by: Arc<OnceLock<String>> = Arc::new(OnceLock::new());
+    let released = Arc::new(Notify::new());
+
+    // Admit checks longest-expected-first so the critical path starts as early as possible.
+    let mut pending: Vec<(usize, Check, QueueTicket)> = selected
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(idx, check)| (idx, check, pool.enqueue()))
+        .collect();
+    pending.sort_by_key(|(_, check, _)| Reverse(admission_key(check, &expected)));
+
+    let mut handles: Vec<Option<JoinHandle<CheckResult>>> = selected.iter().map(|_| None).collect();
+    while !pending.is_empty() {
+        if cancel.is_cancelled() {
+            // Let the remaining checks report themselves as cancelled.
+            for (idx, check, _ticket) in pending.drain(..) {
+                let expected = expected.get(&check.name).copied();
+                handles[idx] = Some(tokio::spawn(execute_check(
+                    check,
+                    cancel.clone(),
+                    expected,
+                    ctx.clone(),
+                )));
             }
+            break;
+        }
 
-            // Pass UI channel for streaming
-            let result = execution::run_single_check(&check_clone, &root, ui_tx.clone()).await;
-
-            // Convert errors to failing CheckResult so they're not lost
-            let check_result = match result {
-                Ok(res) => res,
-                Err(err) => {
-                    let error_msg = format!("{err:#}");
-                    // Stream the error so it shows in verbose mode
-                    if let Some(tx) = ui_tx.as_ref() {
-                        let _ = tx
-                            .send(UiEvent::StreamLine {
-                                source: check_clone.name.clone(),
-                                stream: crate::ui::StreamType::Stderr,
-                                line: error_msg.clone(),
-                            })
-                            .await;
+        // Take the first pending check whose lock group has room; checks waiting on a
+        // lock must not hold up the ones behind them.
+        let mut admitted = None;
+        for (pos, (_, check, _)) in pending.iter().enumerate() {
+            match check.lock.as_deref() {
+                Some(lock) => {
+                    let group = lock_groups.get(lock).expect("lock group present").clone();
+                    if let Ok(permit) = group.try_acquire_owned() {
+                        admitted = Some((pos, Some(permit)));
+                        break;
                     }
-                    synthesize_failed_result(check_clone.clone(), &error_msg)
                 }
-            };
+                None => {
+                    admitted = Some((pos, None));
+                    break;
+                }
+            }
+        }
 
-            if let Some(tx) = ui_tx.as_ref() {
-                let success = check_result.exit_code == Some(0)
-                    && !check_result
-                        .annotations
-                        .iter()
-                        .any(|a| is_error_level(a.level));
-                let msg = if success {
-                    "ok".to_string()
-                } else if check_result.exit_code.is_none() {
-                    // Execution failure (not a normal exit)
-                    "failed to run".to_string()
-                } else {
-                    format!("{} issues", check_result.annotations.len())
-                };
-                let output = Some(sanitize_text_for_tui(&check_result.raw_output));
-                let _ = tx
-                    .send(UiEvent::CheckFinished {
-                        name: check_clone.name.clone(),
-                        success,
-                        message: msg,
-                        output,
-                    })
-                    .await;
+        let Some((pos, lock_permit)) = admitted else {
+            tokio::select! {
+                _ = released.notified() => {}
+                _ = cancel.cancelled() => {}
             }
+            continue;
+        };
 
-            check_result
-        });
+        let (idx, check, ticket) = pending.remove(pos);
+        let admission = tokio::select! {
+            admission = pool.admit(demand(&check)) => admission,
+            _ = cancel.cancelled() => {
+                // Stopped while waiting for room; report it with the other leftovers.
+                pending.insert(pos, (idx, check, ticket));
+                continue;
+            }
+        };
+        drop(ticket);
 
-        handles.push((check_for_join, handle));
+        let lock = lock_permit.map(|permit| LockRelease {
+            permit: Some(permit),
+            released: released.clone(),
+        });
+        let expected = expected.get(&check.name).copied();
+        let stop_run = options
+            .stops_on_failure(&check)
+            .then(|| (cancel.clone(), stopped_by.clone()));
+        let task = execute_check(check, cancel.clone(), expected, ctx.clone());
+        handles[idx] = Some(pool.spawn_admitted(admission, async move {
+            let _lock = lock;
+            let result = task.await;
+            if let Some((cancel, stopped_by)) = stop_run
+                && !result.cancelled
+                && !result.passed()
+                && stopped_by.set(result.check.name.clone()).is_ok()
+            {
+                cancel.cancel();
+            }
+            result
+        }));
     }
 
     // Collect results - all checks are included, even those that failed to execute
     let mut results = Vec::new();
-    for (check, handle) in handles {
+    for (check, handle) in selected.into_iter().zip(handles) {
+        let handle = handle.expect("every check is spawned");
         match handle.await {
             Ok(result) => results.push(result),
             Err(join_err) => {
@@ -187,9 +291,12 @@ pub async fn run_checks(
                             success: false,
                             message: "panic".to_string(),
                             output: Some(msg.clone()),
+                            flaky: false,
+                            advisory: check.is_advisory(),
+                            log_path: None,
                         })
                         .await;
-                } else if !quiet {
+                } else if !options.quiet {
                     eprintln!("check task panic for {}: {join_err:?}", check.name);
                 }
                 results.push(synthesize_failed_result(check, &msg));
@@ -197,5 +304,534 @@ pub async fn run_checks(
         }
     }
 
+    if let Some(tx) = ui_events.as_ref() {
+        let cancelled = results.iter().filter(|r| r.cancelled).count();
+        let passed = results.iter().filter(|r| r.passed()).count();
+        let failed = results.iter().filter(|r| r.fails_run()).count();
+        let _ = tx
+            .send(UiEvent::RunSummary {
+                passed,
+                failed,
+                warnings: results.len() - passed - failed - cancelled,
+                cancelled,
+                stopped_by: stopped_by.get().cloned(),
+            })
+            .await;
+    }
+
     results
 }
+
+/// Sort key for admission: checks without history come first (they may well be long, and
+/// running them establishes a baseline), then the longest expected durations.
+fn admission_key(check: &Check, expected: &HashMap<String, Duration>) -> Duration {
+    expected.get(&check.name).copied().unwrap_or(Duration::MAX)
+}
+
+/// Estimate the wall time of a run by simulating longest-first admission on the pool's
+/// slots. Returns `None` unless every check has a duration history.
+fn estimate_run(
+    checks: &[Check],
+    expected: &HashMap<String, Duration>,
+    capacity: usize,
+) -> Option<Duration> {
+    let mut jobs = checks
+        .iter()
+        .map(|check| Some((*expected.get(&check.name)?, check.weight as usize)))
+        .collect::<Option<Vec<_>>>()?;
+    jobs.sort_by_key(|(duration, _)| Reverse(*duration));
+
+    let capacity = capacity.max(1);
+    let mut slots = vec![Duration::ZERO; capacity];
+    for (duration, weight) in jobs {
+        slots.sort();
+        let weight = weight.clamp(1, capacity);
+        let start = slots[weight - 1];
+        for slot in &mut slots[..weight] {
+            *slot = start + duration;
+        }
+    }
+    slots.into_iter().max()
+}
+
+/// Holds a lock group permit and wakes the admission loop once it is released.
+struct LockRelease {
+    permit: Option<OwnedSemaphorePermit>,
+    released: Arc<Notify>,
+}
+
+impl Drop for LockRelease {
+    fn drop(&mut self) {
+        // Release before notifying so the woken admission loop can take the permit.
+        drop(self.permit.take());
+        self.released.notify_one();
+    }
+}
+
+/// Lock semaphores keyed by group name, shared between the checks of a run.
+pub type LockGroups = Arc<HashMap<String, Arc<Semaphore>>>;
+
+/// Build the optional per-check lock groups used to limit contended tools/resources.
+///
+/// Each group admits `capacities[name]` checks at a time (one if not declared).
+pub fn lock_groups(checks: &[Check], capacities: &HashMap<String, usize>) -> LockGroups {
+    let mut groups: HashMap<String, Arc<Semaphore>> = HashMap::new();
+    for check in checks {
+        if let Some(lock) = check.lock.as_ref() {
+            groups.entry(lock.clone()).or_insert_with(|| {
+                let capacity = capacities.get(lock).copied().unwrap_or(1);
+                Arc::new(Semaphore::new(capacity))
+            });
+        }
+    }
+    Arc::new(groups)
+}
+
+/// Pool resources a check needs before it may start.
+fn demand(check: &Check) -> Demand {
+    Demand {
+        slots: check.weight,
+        memory_mb: check.memory_mb.unwrap_or(0),
+    }
+}
+
+/// Spawn a single check through the pool and report its progress to the UI.
+///
+/// Aborting the returned handle stops the check and kills its running process.
+pub fn spawn_check(
+    check: Check,
+    pool: &Pool,
+    lock_groups: LockGroups,
+    expected: Option<Duration>,
+    ctx: CheckContext,
+) -> JoinHandle<CheckResult> {
+    let cancel = pool.cancel_token();
+
+    // Spawn through the pool - waits for enough slots (and memory) to be free
+    pool.spawn_with(demand(&check), async move {
+        let _lock_permit = match check.lock.as_deref() {
+            Some(lock) => {
+                let group = lock_groups.get(lock).expect("lock group present").clone();
+                tokio::select! {
+                    permit = group.acquire_owned() => permit.ok(),
+                    _ = cancel.cancelled() => None,
+                }
+            }
+            None => None,
+        };
+
+        execute_check(check, cancel, expected, ctx).await
+    })
+}
+
+/// Run an admitted check, reporting its progress to the UI.
+async fn execute_check(
+    check: Check,
+    cancel: CancellationToken,
+    expected: Option<Duration>,
+    ctx: CheckContext,
+) -> CheckResult {
+    let ui_tx = ctx.ui_tx.clone();
+    // Don't start anything once the run has been cancelled.
+    if cancel.is_cancelled() {
+        if let Some(tx) = ui_tx.as_ref() {
+            let _ = tx
+                .send(UiEvent::CheckCancelled {
+                    name: check.name.clone(),
+                })
+                .await;
+        }
+        return cancelled_result(check);
+    }
+
+    if let Some(tx) = ui_tx.as_ref() {
+        let _ = tx
+            .send(UiEvent::CheckStarted {
+                name: check.name.clone(),
+                desc: check.description.clone(),
+                expected,
+            })
+            .await;
+    } else if !ctx.quiet {
+        eprintln!("running check: {}", check.name);
+    }
+
+    let started = Instant::now();
+    // Pass UI channel for streaming
+    let result = execution::run_single_check(&check, &ctx, &cancel).await;
+
+    // Convert errors to failing CheckResult so they're not lost
+    let mut check_result = match result {
+        Ok(res) => res,
+        Err(err)
+            if matches!(
+                err.downcast_ref::<ProcessError>(),
+                Some(ProcessError::Cancelled)
+            ) =>
+        {
+            if let Some(tx) = ui_tx.as_ref() {
+                let _ = tx
+                    .send(UiEvent::CheckCancelled {
+                        name: check.name.clone(),
+                    })
+                    .await;
+            }
+            return cancelled_result(check);
+        }
+        Err(err) => {
+            let error_msg = format!("{err:#}");
+            // Stream the error so it shows in verbose mode
+            if let Some(tx) = ui_tx.as_ref() {
+                let _ = tx
+                    .send(UiEvent::StreamLine {
+                        source: check.name.clone(),
+                        stream: crate::ui::StreamType::Stderr,
+                        line: error_msg.clone(),
+                    })
+                    .await;
+            }
+            synthesize_failed_result(check.clone(), &error_msg)
+        }
+    };
+    check_result.duration = started.elapsed();
+
+    if let Some(tx) = ui_tx.as_ref() {
+        let success = check_result.passed();
+        let msg = if success && check_result.flaky {
+            format!("ok (flaky: passed on attempt {})", check_result.attempts)
+        } else if success {
+            "ok".to_string()
+        } else if check_result.exit_code.is_none() {
+            // Execution failure (not a normal exit)
+            "failed to run".to_string()
+        } else if check_result.tool_failed() {
+            format!(
+                "tool error (exit {})",
+                check_result.exit_code.unwrap_or_default()
+            )
+        } else {
+            format!("{} issues", check_result.annotations.len())
+        };
+        let output = Some(sanitize_text_for_tui(&check_result.raw_output));
+        let _ = tx
+            .send(UiEvent::CheckFinished {
+                name: check.name.clone(),
+                success,
+                message: msg,
+                output,
+                flaky: check_result.flaky,
+                advisory: check.is_advisory(),
+                log_path: check_result.log_path.clone(),
+            })
+            .await;
+    }
+
+    check_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandSpec;
+
+    fn make_check(name: &str, weight: u32) -> Check {
+        Check {
+            name: name.to_string(),
+            command: CommandSpec {
+                program: "echo".to_string(),
+                args: vec![],
+            },
+            formatter: None,
+            fixer: None,
+            env: HashMap::new(),
+            timeout: None,
+            kill_grace: None,
+            enabled: true,
+            tags: vec![],
+            description: None,
+            cwd: None,
+            lock: None,
+            weight,
+            memory_mb: None,
+            watch: vec![],
+            retries: 0,
+            retry_on: vec![],
+            critical: false,
+            pty: None,
+            success_exit_codes: vec![0],
+            error_exit_codes: vec![],
+            fail_if_output_matches: vec![],
+            mode: Default::default(),
+            env_policy: Default::default(),
+            fix_scope: Default::default(),
+            on_scope_violation: Default::default(),
+            prompt: Default::default(),
+        }
+    }
+
+    fn secs(entries: &[(&str, u64)]) -> HashMap<String, Duration> {
+        entries
+            .iter()
+            .map(|(name, s)| (name.to_string(), Duration::from_secs(*s)))
+            .collect()
+    }
+
+    fn stop_config(critical: bool) -> Config {
+        Config::from_toml(&format!(
+            r#"
+[[checks]]
+name = "slow"
+command = ["sleep", "30"]
+
+[[checks]]
+name = "broken"
+command = ["sh", "-c", "exit 1"]
+critical = {critical}
+"#
+        ))
+        .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn critical_failure_cancels_outstanding_checks() {
+        let pool = Pool::new(4);
+        let root = std::env::temp_dir();
+        let started = Instant::now();
+
+        let results = run_checks(
+            &stop_config(true),
+            &[],
+            RunOptions::default(),
+            &pool,
+            None,
+            &root,
+        )
+        .await;
+
+        assert!(started.elapsed() < Duration::from_secs(15));
+        assert!(!pool.is_cancelled());
+        let slow = results.iter().find(|r| r.check.name == "slow").unwrap();
+        let broken = results.iter().find(|r| r.check.name == "broken").unwrap();
+        assert!(slow.cancelled);
+        assert!(!broken.cancelled && !broken.passed());
+        let stopped_by = RunOptions::default().stopped_by(&results).unwrap();
+        assert_eq!(stopped_by.check.name, "broken");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keep_going_ignores_critical() {
+        let pool = Pool::new(4);
+        let root = std::env::temp_dir();
+        let mut config = stop_config(true);
+        config.checks[0].command.args = vec!["0.2".to_string()];
+
+        let options = RunOptions {
+            keep_going: true,
+            ..RunOptions::default()
+        };
+        let results = run_checks(&config, &[], options.clone(), &pool, None, &root).await;
+
+        assert!(results.iter().all(|r| !r.cancelled));
+        assert!(options.stopped_by(&results).is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exit_codes_and_output_assertions_decide_success() {
+        let config = Config::from_toml(
+            r#"
+[[checks]]
+name = "findings-ok"
+command = ["sh", "-c", "exit 1"]
+success_exit_codes = [0, 1]
+
+[[checks]]
+name = "crashed"
+command = ["sh", "-c", "echo '::error file=a.rs,line=1::boom'; exit 2"]
+error_exit_codes = [2]
+
+[[checks]]
+name = "quiet-errors"
+command = ["sh", "-c", "echo 'ERROR: something broke'"]
+fail_if_output_matches = ["^ERROR:"]
+"#,
+        )
+        .unwrap();
+        let pool = Pool::new(4);
+        let root = std::env::temp_dir();
+        let results = run_checks(&config, &[], RunOptions::default(), &pool, None, &root).await;
+        let result = |name: &str| results.iter().find(|r| r.check.name == name).unwrap();
+
+        assert!(result("findings-ok").passed());
+
+        let crashed = result("crashed");
+        assert!(!crashed.passed() && crashed.tool_failed());
+        assert!(crashed.annotations.iter().all(|a| !a.actionable));
+
+        let quiet = result("quiet-errors");
+        assert_eq!(quiet.exit_code, Some(0));
+        assert!(!quiet.passed());
+        assert!(quiet.annotations[0].message.contains("something broke"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fixer_is_not_run_when_the_tool_failed() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "scanner-rs-tool-failed-{}-{nanos}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        let config = Config::from_toml(
+            r#"
+[[checks]]
+name = "crashed"
+command = ["sh", "-c", "echo '::error file=a.rs,line=1::boom'; exit 2"]
+error_exit_codes = [2]
+fixer = ["touch", "fixed"]
+"#,
+        )
+        .unwrap();
+        let pool = Pool::new(1);
+        let results = run_checks(&config, &[], RunOptions::default(), &pool, None, &root).await;
+
+        assert!(results[0].tool_failed());
+        assert!(!root.join("fixed").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn the_rerun_after_the_fixer_is_retried_too() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "scanner-rs-fixer-retries-{}-{nanos}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        // Fails its first three runs: both attempts before the fixer and the first after.
+        let config = Config::from_toml(
+            r#"
+[[checks]]
+name = "flaky"
+command = ["sh", "-c", "n=$(($(cat count 2>/dev/null || echo 0) + 1)); echo $n > count; [ $n -ge 4 ]"]
+fixer = ["true"]
+retries = 1
+"#,
+        )
+        .unwrap();
+        let pool = Pool::new(1);
+        let results = run_checks(&config, &[], RunOptions::default(), &pool, None, &root).await;
+
+        assert!(results[0].passed());
+        assert_eq!(results[0].attempts, 2);
+        assert!(results[0].flaky);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn findings_in_the_dropped_middle_of_huge_output_count() {
+        let config = Config::from_toml(
+            r#"
+[output]
+max_bytes = "1KB"
+keep_logs = 0
+
+[[checks]]
+name = "noisy"
+command = ["sh", "-c", "seq 1 2000; echo '::error file=a.rs,line=3::boom'; echo 'FATAL: disk'; seq 1 2000"]
+success_exit_codes = [0]
+fail_if_output_matches = ["^FATAL:"]
+"#,
+        )
+        .unwrap();
+        let pool = Pool::new(1);
+        let root = std::env::temp_dir();
+        let results = run_checks(&config, &[], RunOptions::default(), &pool, None, &root).await;
+        let noisy = &results[0];
+
+        assert!(noisy.raw_output.contains("bytes omitted"));
+        assert!(!noisy.raw_output.contains("boom"));
+        assert!(!noisy.passed());
+        let messages: Vec<&str> = noisy
+            .annotations
+            .iter()
+            .map(|a| a.message.as_str())
+            .collect();
+        assert!(messages.contains(&"boom"), "{messages:?}");
+        assert!(
+            messages.iter().any(|m| m.contains("FATAL: disk")),
+            "{messages:?}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn advisory_failure_neither_stops_nor_fails_the_run() {
+        let pool = Pool::new(4);
+        let root = std::env::temp_dir();
+        let mut config = stop_config(false);
+        config.checks[0].command.args = vec!["0.2".to_string()];
+        config.checks[1].mode = crate::config::CheckMode::Advisory;
+
+        let options = RunOptions {
+            fail_fast: true,
+            ..RunOptions::default()
+        };
+        let results = run_checks(&config, &[], options, &pool, None, &root).await;
+
+        assert!(results.iter().all(|r| !r.cancelled));
+        let broken = results.iter().find(|r| r.check.name == "broken").unwrap();
+        assert!(!broken.passed() && !broken.fails_run());
+    }
+
+    #[test]
+    fn admission_puts_unknown_then_longest_first() {
+        let expected = secs(&[("lint", 5), ("test", 300), ("fmt", 1)]);
+        let mut checks = [
+            make_check("lint", 1),
+            make_check("fmt", 1),
+            make_check("new", 1),
+            make_check("test", 1),
+        ];
+        checks.sort_by_key(|check| Reverse(admission_key(check, &expected)));
+        let names: Vec<_> = checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["new", "test", "lint", "fmt"]);
+    }
+
+    #[test]
+    fn estimate_simulates_longest_first_on_slots() {
+        let checks = vec![make_check("a", 1), make_check("b", 1), make_check("c", 1)];
+        let expected = secs(&[("a", 10), ("b", 6), ("c", 4)]);
+        // Two slots: a runs alone, b then c share the other slot.
+        assert_eq!(
+            estimate_run(&checks, &expected, 2),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            estimate_run(&checks, &expected, 1),
+            Some(Duration::from_secs(20))
+        );
+
+        // A heavy check needs both slots at once.
+        let heavy = vec![make_check("a", 2), make_check("b", 1)];
+        assert_eq!(
+            estimate_run(&heavy, &expected, 2),
+            Some(Duration::from_secs(16))
+        );
+
+        // Without a history for every check there is no estimate.
+        let unknown = vec![make_check("a", 1), make_check("new", 1)];
+        assert_eq!(estimate_run(&unknown, &expected, 2), None);
+    }
+}
@@ -48,16 +48,38 @@ mod tests {
             fixer: None,
             env: HashMap::new(),
             timeout: None,
+            kill_grace: None,
             enabled,
             tags: tags.into_iter().map(String::from).collect(),
             description: None,
             cwd: None,
             lock: None,
+            weight: 1,
+            memory_mb: None,
+            watch: vec![],
+            retries: 0,
+            retry_on: vec![],
+            critical: false,
+            pty: None,
+            success_exit_codes: vec![0],
+            error_exit_codes: vec![],
+            fail_if_output_matches: vec![],
+            mode: Default::default(),
+            env_policy: Default::default(),
+            fix_scope: Default::default(),
+            on_scope_violation: Default::default(),
+            prompt: Default::default(),
         }
     }
 
     fn make_config(checks: Vec<Check>) -> Config {
         Config {
+            env_policy: Default::default(),
+            pool: Default::default(),
+            output: Default::default(),
+            redaction: Default::default(),
+            costs: Default::default(),
+            locks: HashMap::new(),
             setup: Vec::new(),
             checks,
             agent: None,
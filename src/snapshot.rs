@@ -0,0 +1,406 @@
+//! `--snapshot`: run checks and solvers in a temporary git worktree so edits made to the
+//! working copy during a run can't race with them, and solver changes only land once the
+//! developer accepts them.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+
+use crate::error::SnapshotError;
+
+/// A temporary worktree holding a copy of the working tree (committed, staged, unstaged
+/// and untracked files) as it was when the run started. Initialized submodules get
+/// worktrees of their own inside it.
+///
+/// Creating, diffing and dropping a snapshot run git and block; from async code, do it on
+/// a blocking thread.
+pub struct Snapshot {
+    /// Top level of the developer's repository.
+    repo: PathBuf,
+    worktree: PathBuf,
+    /// The scanner root, relative to the repository top level.
+    prefix: PathBuf,
+    /// Tree object of the snapshot's initial content; changes are diffed against it.
+    base_tree: String,
+    /// Snapshots of initialized submodules, by their path relative to `worktree`.
+    submodules: Vec<(String, Snapshot)>,
+}
+
+impl Snapshot {
+    pub fn create(root: &Path) -> Result<Self> {
+        let repo = git(root, &["rev-parse", "--show-toplevel"])
+            .map_err(|_| SnapshotError::NotARepository(root.to_path_buf()))?;
+        let repo = PathBuf::from(text(&repo));
+
+        let canonical_root = root.canonicalize()?;
+        let prefix = canonical_root
+            .strip_prefix(repo.canonicalize()?)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        // An empty, private directory with a name nobody can guess.
+        let worktree = tempfile::Builder::new()
+            .prefix("scanner-snapshot-")
+            .tempdir()?
+            .keep();
+        let mut snapshot = match Self::check_out(repo, worktree.clone()) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&worktree);
+                return Err(e);
+            }
+        };
+        snapshot.prefix = prefix;
+        Ok(snapshot)
+    }
+
+    /// Add a worktree of `repo` at `worktree` (an empty directory) and copy the uncommitted
+    /// work of `repo` into it.
+    fn check_out(repo: PathBuf, worktree: PathBuf) -> Result<Self> {
+        git(&repo, &["rev-parse", "--verify", "--quiet", "HEAD"])
+            .map_err(|_| SnapshotError::NoCommits(repo.clone()))?;
+        git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                "--quiet",
+                &worktree.to_string_lossy(),
+                "HEAD",
+            ],
+        )?;
+        // From here on, dropping the snapshot removes the worktree again.
+        let mut snapshot = Self {
+            repo,
+            worktree,
+            prefix: PathBuf::new(),
+            base_tree: String::new(),
+            submodules: Vec::new(),
+        };
+
+        // Staged and unstaged changes to tracked files...
+        let changes = git(
+            &snapshot.repo,
+            &["diff", "--binary", "--ignore-submodules=all", "HEAD"],
+        )?;
+        if !changes.is_empty() {
+            git_with_input(&snapshot.worktree, &["apply", "--binary"], &changes)?;
+        }
+        // ...and untracked files that aren't ignored. Untracked directories only show up
+        // here when they hold a repository of their own, which isn't copied.
+        let untracked = git(
+            &snapshot.repo,
+            &["ls-files", "--others", "--exclude-standard", "-z"],
+        )?;
+        for name in untracked.split(|b| *b == 0).filter(|name| !name.is_empty()) {
+            let name = text(name);
+            let source = snapshot.repo.join(&name);
+            // Gone since it was listed, or a nested repository.
+            let Ok(meta) = std::fs::symlink_metadata(&source) else {
+                continue;
+            };
+            if meta.is_dir() {
+                continue;
+            }
+            let target = snapshot.worktree.join(&name);
+            if let Some(dir) = target.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            copy_entry(&source, &target, &meta)?;
+        }
+        // Initialized submodules, each with its own uncommitted work.
+        let staged = git(&snapshot.repo, &["ls-files", "--stage", "-z"])?;
+        for entry in staged.split(|b| *b == 0) {
+            let entry = String::from_utf8_lossy(entry);
+            let Some(("160000", path)) = entry
+                .split_once('\t')
+                .and_then(|(info, path)| Some((info.split(' ').next()?, path)))
+            else {
+                continue;
+            };
+            if !snapshot.repo.join(path).join(".git").exists() {
+                continue;
+            }
+            let submodule =
+                Self::check_out(snapshot.repo.join(path), snapshot.worktree.join(path))?;
+            snapshot.submodules.push((path.to_string(), submodule));
+        }
+
+        git(&snapshot.worktree, &["add", "--all"])?;
+        snapshot.base_tree = text(&git(&snapshot.worktree, &["write-tree"])?);
+        Ok(snapshot)
+    }
+
+    /// Top level of the developer's repository (where the patch applies).
+    pub fn repo(&self) -> &Path {
+        &self.repo
+    }
+
+    /// The scanner root inside the snapshot.
+    pub fn root(&self) -> PathBuf {
+        self.worktree.join(&self.prefix)
+    }
+
+    /// Everything changed inside the snapshot since it was created, as a binary patch that
+    /// applies to the repository top level. Changes inside submodules are included.
+    pub fn diff(&self) -> Result<Vec<u8>> {
+        self.diff_under("")
+    }
+
+    /// [`Snapshot::diff`] with paths prefixed by `dir` (a submodule's path, with a slash).
+    fn diff_under(&self, dir: &str) -> Result<Vec<u8>> {
+        git(&self.worktree, &["add", "--all"])?;
+        let mut patch = git(
+            &self.worktree,
+            &[
+                "diff",
+                "--cached",
+                "--binary",
+                "--ignore-submodules=all",
+                &format!("--src-prefix=a/{dir}"),
+                &format!("--dst-prefix=b/{dir}"),
+                &self.base_tree,
+            ],
+        )?;
+        for (path, submodule) in &self.submodules {
+            patch.extend(submodule.diff_under(&format!("{dir}{path}/"))?);
+        }
+        Ok(patch)
+    }
+
+    /// A `git diff --stat` summary of the changes.
+    pub fn diff_stat(&self) -> Result<String> {
+        let stat = git_with_input(&self.worktree, &["apply", "--stat"], &self.diff()?)?;
+        Ok(String::from_utf8_lossy(&stat).into_owned())
+    }
+
+    /// Apply a patch from [`Snapshot::diff`] to the developer's working copy. Nothing is
+    /// changed if any hunk fails to apply.
+    pub fn apply(&self, patch: &[u8]) -> Result<()> {
+        git_with_input(&self.repo, &["apply", "--binary"], patch)
+            .map_err(|e| SnapshotError::ApplyFailed(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Apply a patch from [`Snapshot::diff`] to the repository containing `dir`. Hunk lengths
+/// are recounted, so hunks edited by hand still apply.
+pub fn apply_to(dir: &Path, patch: &[u8]) -> Result<()> {
+    let repo = git(dir, &["rev-parse", "--show-toplevel"])
+        .map_err(|_| SnapshotError::NotARepository(dir.to_path_buf()))?;
+    git_with_input(
+        Path::new(&text(&repo)),
+        &["apply", "--binary", "--recount"],
+        patch,
+    )
+    .map_err(|e| SnapshotError::ApplyFailed(e.to_string()))?;
+    Ok(())
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        // Submodule worktrees live inside this one; remove them first.
+        self.submodules.clear();
+        let removed = git(
+            &self.repo,
+            &[
+                "worktree",
+                "remove",
+                "--force",
+                &self.worktree.to_string_lossy(),
+            ],
+        );
+        if removed.is_err() {
+            let _ = std::fs::remove_dir_all(&self.worktree);
+            let _ = git(&self.repo, &["worktree", "prune"]);
+        }
+    }
+}
+
+/// Copy an untracked file into the snapshot, recreating symlinks as symlinks (dangling
+/// ones included) instead of copying what they point to.
+fn copy_entry(source: &Path, target: &Path, meta: &std::fs::Metadata) -> Result<()> {
+    #[cfg(unix)]
+    if meta.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(source)?, target)?;
+        return Ok(());
+    }
+    let _ = meta;
+    std::fs::copy(source, target)?;
+    Ok(())
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim().to_string()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    git_with_input(dir, args, &[])
+}
+
+fn git_with_input(dir: &Path, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SnapshotError::Git {
+            command: args.join(" "),
+            reason: e.to_string(),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(SnapshotError::Git {
+            command: args.join(" "),
+            reason: text(&output.stderr),
+        }
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    fn temp_repo(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir =
+            std::env::temp_dir().join(format!("scanner-rs-{name}-{}-{nanos}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet"]).unwrap();
+        for (key, value) in [("user.name", "test"), ("user.email", "test@example.com")] {
+            git(&dir, &["config", key, value]).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn snapshot_copies_uncommitted_work_and_applies_changes_back() {
+        let repo = temp_repo("snapshot");
+        std::fs::write(repo.join("tracked.txt"), "one\n").unwrap();
+        std::fs::write(repo.join(".gitignore"), "ignored.txt\n").unwrap();
+        git(&repo, &["add", "--all"]).unwrap();
+        git(&repo, &["commit", "--quiet", "-m", "init"]).unwrap();
+
+        std::fs::write(repo.join("tracked.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(repo.join("new.txt"), "new\n").unwrap();
+        std::fs::write(repo.join("ignored.txt"), "secret\n").unwrap();
+
+        let snapshot = Snapshot::create(&repo).unwrap();
+        let root = snapshot.root();
+        assert_eq!(
+            std::fs::read_to_string(root.join("tracked.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(root.join("new.txt").exists());
+        assert!(!root.join("ignored.txt").exists());
+        assert!(snapshot.diff().unwrap().is_empty());
+
+        // A "fix" inside the snapshot leaves the working copy alone until applied.
+        std::fs::write(root.join("tracked.txt"), "one\ntwo\nthree\n").unwrap();
+        let patch = snapshot.diff().unwrap();
+        assert!(snapshot.diff_stat().unwrap().contains("tracked.txt"));
+        assert_eq!(
+            std::fs::read_to_string(repo.join("tracked.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        snapshot.apply(&patch).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.join("tracked.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+
+        drop(snapshot);
+        assert!(!root.exists());
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn untracked_symlinks_and_nested_repositories_are_handled() {
+        let repo = temp_repo("snapshot-links");
+        std::fs::write(repo.join("tracked.txt"), "one\n").unwrap();
+        git(&repo, &["add", "--all"]).unwrap();
+        git(&repo, &["commit", "--quiet", "-m", "init"]).unwrap();
+
+        std::os::unix::fs::symlink("tracked.txt", repo.join("link")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", repo.join("dangling")).unwrap();
+        let nested = repo.join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        git(&nested, &["init", "--quiet"]).unwrap();
+        std::fs::write(nested.join("inner.txt"), "inner\n").unwrap();
+
+        let snapshot = Snapshot::create(&repo).unwrap();
+        let root = snapshot.root();
+        assert_eq!(
+            std::fs::read_link(root.join("link")).unwrap(),
+            Path::new("tracked.txt")
+        );
+        assert_eq!(
+            std::fs::read_link(root.join("dangling")).unwrap(),
+            Path::new("missing.txt")
+        );
+        assert!(!root.join("nested/inner.txt").exists());
+        drop(snapshot);
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn submodules_are_carried_into_the_snapshot() {
+        let inner = temp_repo("snapshot-inner");
+        std::fs::write(inner.join("lib.txt"), "one\n").unwrap();
+        git(&inner, &["add", "--all"]).unwrap();
+        git(&inner, &["commit", "--quiet", "-m", "init"]).unwrap();
+
+        let repo = temp_repo("snapshot-outer");
+        git(
+            &repo,
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "--quiet",
+                &inner.to_string_lossy(),
+                "sub",
+            ],
+        )
+        .unwrap();
+        git(&repo, &["commit", "--quiet", "-m", "init"]).unwrap();
+        std::fs::write(repo.join("sub/lib.txt"), "one\ntwo\n").unwrap();
+
+        let snapshot = Snapshot::create(&repo).unwrap();
+        let root = snapshot.root();
+        assert_eq!(
+            std::fs::read_to_string(root.join("sub/lib.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        std::fs::write(root.join("sub/lib.txt"), "one\ntwo\nthree\n").unwrap();
+        snapshot.apply(&snapshot.diff().unwrap()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(repo.join("sub/lib.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+
+        drop(snapshot);
+        assert!(!root.exists());
+        let _ = std::fs::remove_dir_all(&repo);
+        let _ = std::fs::remove_dir_all(&inner);
+    }
+}
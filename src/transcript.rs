@@ -0,0 +1,370 @@
+//! Agent output read as a sequence of steps: codex `--json` events and claude
+//! `stream-json` messages become messages, tool calls, file edits, token usage and a final
+//! summary, so the UI can show what a solver does instead of raw JSON.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::AgentOutput;
+
+/// One thing an agent said or did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentStep {
+    /// Text addressed to the user.
+    Message(String),
+    /// A tool or command the agent ran.
+    ToolCall { tool: String, detail: String },
+    /// A file the agent created, changed or deleted.
+    FileEdit { path: String },
+    /// Tokens spent so far (for one turn, or the whole run).
+    Usage(TokenUsage),
+    /// Something went wrong on the agent's side.
+    Error(String),
+    /// The agent's closing explanation of what it did.
+    Summary(String),
+}
+
+/// Tokens reported by an agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    #[serde(rename = "input_tokens")]
+    pub input: u64,
+    /// Part of `input` served from the provider's cache.
+    #[serde(rename = "cached_input_tokens")]
+    pub cached_input: u64,
+    #[serde(rename = "output_tokens")]
+    pub output: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input + self.output
+    }
+
+    pub fn add(&mut self, other: TokenUsage) {
+        self.input += other.input;
+        self.cached_input += other.cached_input;
+        self.output += other.output;
+    }
+}
+
+impl fmt::Display for AgentStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(text) => write!(f, "{text}"),
+            Self::ToolCall { tool, detail } if detail.is_empty() => write!(f, "$ {tool}"),
+            Self::ToolCall { tool, detail } => write!(f, "$ {tool}: {detail}"),
+            Self::FileEdit { path } => write!(f, "edited {path}"),
+            Self::Usage(usage) => write!(
+                f,
+                "tokens: {} in ({} cached), {} out",
+                usage.input, usage.cached_input, usage.output
+            ),
+            Self::Error(text) => write!(f, "error: {text}"),
+            Self::Summary(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+/// Everything an agent run produced, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub steps: Vec<AgentStep>,
+}
+
+impl Transcript {
+    /// The agent's final word: its summary, or failing that its last message.
+    pub fn explanation(&self) -> Option<&str> {
+        let last = |want_summary: bool| {
+            self.steps.iter().rev().find_map(|step| match step {
+                AgentStep::Summary(text) if want_summary => Some(text.as_str()),
+                AgentStep::Message(text) if !want_summary => Some(text.as_str()),
+                _ => None,
+            })
+        };
+        last(true).or_else(|| last(false))
+    }
+
+    /// Tokens reported over the whole run.
+    pub fn usage(&self) -> TokenUsage {
+        let mut usage = TokenUsage::default();
+        for step in &self.steps {
+            if let AgentStep::Usage(turn) = step {
+                usage.add(*turn);
+            }
+        }
+        usage
+    }
+
+    /// The last error the agent reported.
+    pub fn error(&self) -> Option<&str> {
+        self.steps.iter().rev().find_map(|step| match step {
+            AgentStep::Error(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// A readable log: one line per tool call and edit, messages in full, and the
+    /// explanation last.
+    pub fn render(&self) -> String {
+        let explanation = self.explanation();
+        let mut lines: Vec<String> = self
+            .steps
+            .iter()
+            .filter(|step| match step {
+                AgentStep::Summary(_) => false,
+                AgentStep::Message(text) => Some(text.as_str()) != explanation,
+                _ => true,
+            })
+            .map(ToString::to_string)
+            .collect();
+        if let Some(explanation) = explanation {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(explanation.to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+/// The steps in one line of agent output. Lines that aren't JSON are kept as messages.
+pub fn parse_line(format: AgentOutput, line: &str) -> Vec<AgentStep> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Vec::new();
+    }
+    let parse = match format {
+        AgentOutput::CodexJson => codex_steps,
+        AgentOutput::ClaudeStreamJson => claude_steps,
+        AgentOutput::Auto | AgentOutput::Text => return vec![AgentStep::Message(line.into())],
+    };
+    match serde_json::from_str::<Value>(line) {
+        Ok(event) => parse(&event),
+        Err(_) => vec![AgentStep::Message(line.to_string())],
+    }
+}
+
+fn text(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn count(value: &Value, key: &str) -> u64 {
+    value.get(key).and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// `codex exec --json`: `item.completed` events carry messages, commands and file
+/// changes; `turn.completed` carries usage.
+fn codex_steps(event: &Value) -> Vec<AgentStep> {
+    match event.get("type").and_then(Value::as_str) {
+        Some("item.completed") => {}
+        Some("turn.completed") => {
+            let Some(usage) = event.get("usage") else {
+                return Vec::new();
+            };
+            return vec![AgentStep::Usage(TokenUsage {
+                input: count(usage, "input_tokens"),
+                cached_input: count(usage, "cached_input_tokens"),
+                output: count(usage, "output_tokens"),
+            })];
+        }
+        Some("turn.failed") => {
+            let error = event.get("error").unwrap_or(&Value::Null);
+            return vec![AgentStep::Error(text(error, "message"))];
+        }
+        Some("error") => return vec![AgentStep::Error(text(event, "message"))],
+        _ => return Vec::new(),
+    }
+
+    let Some(item) = event.get("item") else {
+        return Vec::new();
+    };
+    match item.get("type").and_then(Value::as_str) {
+        Some("agent_message") => vec![AgentStep::Message(text(item, "text"))],
+        Some("command_execution") => vec![AgentStep::ToolCall {
+            tool: "shell".to_string(),
+            detail: text(item, "command"),
+        }],
+        Some("file_change") => item
+            .get("changes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|change| AgentStep::FileEdit {
+                path: text(change, "path"),
+            })
+            .collect(),
+        Some("mcp_tool_call") => vec![AgentStep::ToolCall {
+            tool: format!("{}.{}", text(item, "server"), text(item, "tool")),
+            detail: String::new(),
+        }],
+        Some("web_search") => vec![AgentStep::ToolCall {
+            tool: "web_search".to_string(),
+            detail: text(item, "query"),
+        }],
+        Some("error") => vec![AgentStep::Error(text(item, "message"))],
+        _ => Vec::new(),
+    }
+}
+
+/// Tools whose `file_path` input is a file they write.
+const CLAUDE_EDIT_TOOLS: &[&str] = &["Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+/// `claude --output-format stream-json`: `assistant` messages carry text and tool uses,
+/// the closing `result` carries the summary and the run's usage.
+fn claude_steps(event: &Value) -> Vec<AgentStep> {
+    match event.get("type").and_then(Value::as_str) {
+        Some("assistant") => {}
+        Some("result") => {
+            let mut steps = Vec::new();
+            let result = text(event, "result");
+            if event.get("is_error").and_then(Value::as_bool) == Some(true) {
+                steps.push(AgentStep::Error(result));
+            } else if !result.is_empty() {
+                steps.push(AgentStep::Summary(result));
+            }
+            if let Some(usage) = event.get("usage") {
+                let cached = count(usage, "cache_read_input_tokens");
+                steps.push(AgentStep::Usage(TokenUsage {
+                    input: count(usage, "input_tokens")
+                        + cached
+                        + count(usage, "cache_creation_input_tokens"),
+                    cached_input: cached,
+                    output: count(usage, "output_tokens"),
+                }));
+            }
+            return steps;
+        }
+        _ => return Vec::new(),
+    }
+
+    let content = event
+        .get("message")
+        .and_then(|message| message.get("content"))
+        .and_then(Value::as_array);
+    content
+        .into_iter()
+        .flatten()
+        .filter_map(|block| match block.get("type").and_then(Value::as_str) {
+            Some("text") => Some(AgentStep::Message(text(block, "text"))),
+            Some("tool_use") => {
+                let tool = text(block, "name");
+                let input = block.get("input").unwrap_or(&Value::Null);
+                let path = text(input, "file_path");
+                if CLAUDE_EDIT_TOOLS.contains(&tool.as_str()) && !path.is_empty() {
+                    return Some(AgentStep::FileEdit { path });
+                }
+                let detail = ["command", "file_path", "pattern", "url", "description"]
+                    .iter()
+                    .map(|key| text(input, key))
+                    .find(|value| !value.is_empty())
+                    .unwrap_or_default();
+                Some(AgentStep::ToolCall { tool, detail })
+            }
+            _ => None,
+        })
+        .filter(|step| !matches!(step, AgentStep::Message(text) if text.trim().is_empty()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(format: AgentOutput, output: &str) -> Transcript {
+        Transcript {
+            steps: output
+                .lines()
+                .flat_map(|line| parse_line(format, line))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn codex_events_become_steps() {
+        let output = r#"{"type":"thread.started","thread_id":"t1"}
+{"type":"turn.started"}
+{"type":"item.completed","item":{"id":"item_0","type":"reasoning","text":"thinking"}}
+{"type":"item.started","item":{"id":"item_1","type":"command_execution","command":"cargo check","status":"in_progress"}}
+{"type":"item.completed","item":{"id":"item_1","type":"command_execution","command":"cargo check","exit_code":0,"status":"completed"}}
+{"type":"item.completed","item":{"id":"item_2","type":"file_change","changes":[{"path":"src/a.rs","kind":"update"}],"status":"completed"}}
+{"type":"item.completed","item":{"id":"item_3","type":"agent_message","text":"Removed the unused import."}}
+{"type":"turn.completed","usage":{"input_tokens":1200,"cached_input_tokens":1000,"output_tokens":80}}"#;
+        let transcript = transcript(AgentOutput::CodexJson, output);
+        assert_eq!(
+            transcript.steps,
+            vec![
+                AgentStep::ToolCall {
+                    tool: "shell".to_string(),
+                    detail: "cargo check".to_string(),
+                },
+                AgentStep::FileEdit {
+                    path: "src/a.rs".to_string(),
+                },
+                AgentStep::Message("Removed the unused import.".to_string()),
+                AgentStep::Usage(TokenUsage {
+                    input: 1200,
+                    cached_input: 1000,
+                    output: 80,
+                }),
+            ]
+        );
+        assert_eq!(transcript.explanation(), Some("Removed the unused import."));
+        assert_eq!(
+            transcript.render(),
+            "$ shell: cargo check\nedited src/a.rs\ntokens: 1200 in (1000 cached), 80 out\n\n\
+             Removed the unused import."
+        );
+    }
+
+    #[test]
+    fn claude_stream_json_becomes_steps() {
+        let output = r#"{"type":"system","subtype":"init","session_id":"s1"}
+{"type":"assistant","message":{"content":[{"type":"text","text":"Looking at the error."},{"type":"tool_use","id":"t1","name":"Read","input":{"file_path":"/repo/src/a.rs"}}]}}
+{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"..."}]}}
+{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t2","name":"Edit","input":{"file_path":"/repo/src/a.rs","old_string":"a","new_string":"b"}}]}}
+{"type":"result","subtype":"success","is_error":false,"result":"Fixed the typo in a.rs.","usage":{"input_tokens":10,"cache_read_input_tokens":900,"cache_creation_input_tokens":90,"output_tokens":40}}"#;
+        let transcript = transcript(AgentOutput::ClaudeStreamJson, output);
+        assert_eq!(
+            transcript.steps,
+            vec![
+                AgentStep::Message("Looking at the error.".to_string()),
+                AgentStep::ToolCall {
+                    tool: "Read".to_string(),
+                    detail: "/repo/src/a.rs".to_string(),
+                },
+                AgentStep::FileEdit {
+                    path: "/repo/src/a.rs".to_string(),
+                },
+                AgentStep::Summary("Fixed the typo in a.rs.".to_string()),
+                AgentStep::Usage(TokenUsage {
+                    input: 1000,
+                    cached_input: 900,
+                    output: 40,
+                }),
+            ]
+        );
+        assert_eq!(transcript.explanation(), Some("Fixed the typo in a.rs."));
+        assert_eq!(transcript.error(), None);
+    }
+
+    #[test]
+    fn non_json_lines_are_kept_as_messages() {
+        let steps = parse_line(AgentOutput::CodexJson, "warning: config file ignored");
+        assert_eq!(
+            steps,
+            vec![AgentStep::Message(
+                "warning: config file ignored".to_string()
+            )]
+        );
+        let failed = r#"{"type":"turn.failed","error":{"message":"rate limited"}}"#;
+        let transcript = transcript(AgentOutput::CodexJson, failed);
+        assert_eq!(transcript.error(), Some("rate limited"));
+    }
+}
@@ -2,19 +2,25 @@ use std::time::{Duration, Instant};
 
 use arboard::Clipboard;
 use crossterm::cursor;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
+use ratatui::Terminal;
 use ratatui::prelude::CrosstermBackend;
 use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
 
 use crate::pool::Pool;
+use crate::review::{self, Decision, Hunk};
 use crate::ui::cli;
-use crate::ui::events::UiEvent;
+use crate::ui::events::{StreamType, UiEvent};
 use crate::ui::render::{cleanup_terminal, detail_text, draw};
-use crate::ui::state::{AppState, CheckRow};
+use crate::ui::state::{AppState, CheckRow, ReviewState};
+
+const REVIEW_FOOTER: &str =
+    "Up/Down move | a accept | r reject | e edit | A accept rest | Enter/q done (rest rejected)";
 
 pub fn spawn_ui(
     enable_tui: bool,
@@ -27,6 +33,7 @@ pub fn spawn_ui(
     // Pool stats ticker is only useful in interactive TUI mode.
     if enable_tui {
         let tx_stats = tx.clone();
+        let pool = pool.clone();
         tokio::spawn(async move {
             loop {
                 let stats = pool.stats();
@@ -38,9 +45,10 @@ pub fn spawn_ui(
         });
     }
 
+    let cancel = pool.cancel_token();
     let handle = tokio::spawn(async move {
         if enable_tui {
-            run_tui(rx).await;
+            run_tui(rx, cancel).await;
         } else {
             cli::run_cli(rx, use_color, verbose).await;
         }
@@ -48,7 +56,7 @@ pub fn spawn_ui(
     (Some(tx), handle)
 }
 
-async fn run_tui(mut rx: Receiver<UiEvent>) {
+async fn run_tui(mut rx: Receiver<UiEvent>, cancel: CancellationToken) {
     if enable_raw_mode().is_err() {
         // fallback: consume events and do nothing
         while rx.recv().await.is_some() {}
@@ -97,14 +105,18 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
         // Consume all pending events
         while let Ok(ev) = rx.try_recv() {
             match ev {
-                UiEvent::CheckStarted { name, desc } => {
+                UiEvent::CheckStarted {
+                    name,
+                    desc,
+                    expected,
+                } => {
                     if let Some(row) = state.rows.iter_mut().find(|r| r.name == name) {
-                        row.status = "running".into();
-                        row.success = None;
-                        row.output = Some("running".into());
-                        row.desc = desc;
+                        row.restart(desc);
+                        row.expected = expected;
                     } else {
-                        state.rows.push(CheckRow::new(name, desc));
+                        let mut row = CheckRow::new(name, desc);
+                        row.expected = expected;
+                        state.rows.push(row);
                     }
                 }
                 UiEvent::CheckFinished {
@@ -112,19 +124,70 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
                     success,
                     message,
                     output,
+                    flaky,
+                    advisory,
+                    log_path,
                 } => {
                     if let Some(row) = state.rows.iter_mut().find(|r| r.name == name) {
-                        row.success = Some(success);
-                        row.status = message;
-                        row.output = output;
+                        row.finish(success, message, output, flaky);
+                        row.advisory = advisory;
+                        row.log_path = log_path;
                     } else {
                         let mut row = CheckRow::new(name.clone(), None);
-                        row.success = Some(success);
-                        row.status = message;
-                        row.output = output;
+                        row.finish(success, message, output, flaky);
+                        row.advisory = advisory;
+                        row.log_path = log_path;
                         state.rows.push(row);
                     }
                 }
+                UiEvent::CheckFlagged { name, reason } => {
+                    if let Some(row) = state.rows.iter_mut().find(|r| r.name == name) {
+                        row.flag(reason);
+                    }
+                }
+                UiEvent::CheckCancelled { name } => {
+                    if let Some(row) = state.rows.iter_mut().find(|r| r.name == name) {
+                        row.cancel();
+                    } else {
+                        let mut row = CheckRow::new(name, None);
+                        row.cancel();
+                        state.rows.push(row);
+                    }
+                }
+                UiEvent::RunEstimate { expected } => {
+                    state.run_deadline = Some(Instant::now() + expected);
+                }
+                UiEvent::RunSummary {
+                    passed,
+                    failed,
+                    warnings,
+                    cancelled,
+                    stopped_by,
+                } => {
+                    let mut summary = format!("{passed} passed, {failed} failed");
+                    if warnings > 0 {
+                        summary.push_str(&format!(", {warnings} warnings"));
+                    }
+                    if cancelled > 0 {
+                        summary.push_str(&format!(", {cancelled} cancelled"));
+                    }
+                    if let Some(name) = stopped_by {
+                        summary.push_str(&format!(" (stopped: {name} failed)"));
+                    }
+                    state.summary = Some(summary);
+                }
+                UiEvent::FixRound {
+                    round, max_rounds, ..
+                } => {
+                    state.fix_round = Some((round, max_rounds));
+                }
+                UiEvent::Review { fixes, reply } => {
+                    state.review = Some(ReviewState::new(fixes, reply));
+                    footer_msg = REVIEW_FOOTER.to_string();
+                }
+                UiEvent::SolverCosts(totals) => {
+                    state.solver_costs = Some(totals);
+                }
                 UiEvent::PoolStats(stats) => {
                     state.pool_stats = Some(stats);
                 }
@@ -135,10 +198,17 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
                 } => {
                     state.add_stream_line(source, stream, line);
                 }
+                UiEvent::AgentStep { source, step } => {
+                    state.add_stream_line(source, StreamType::Stdout, step.to_string());
+                }
                 UiEvent::Done => {
                     state.finished = true;
                     footer_msg = "Done | Up/Down move | q/Esc exit | y copy".to_string();
                 }
+                UiEvent::Exit => {
+                    state.finished = true;
+                    state.exit_requested = true;
+                }
             }
         }
 
@@ -157,6 +227,21 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
         if event::poll(Duration::from_millis(50)).unwrap_or(false)
             && let Ok(Event::Key(key)) = event::read()
         {
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if !ctrl_c && let Some(review) = state.review.as_mut() {
+                if let Some(msg) = review_key(&mut terminal, review, key) {
+                    footer_msg = msg;
+                }
+                if matches!(key.code, KeyCode::Enter | KeyCode::Char('q') | KeyCode::Esc)
+                    && let Some(review) = state.review.take()
+                {
+                    review.finish();
+                    footer_msg = "Up/Down move | q/Esc exit (double-press while running) | y copy"
+                        .to_string();
+                }
+                continue;
+            }
             match key.code {
                 // Ctrl+C always quits
                 KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -181,15 +266,11 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
                     footer_msg = "Scanner busy - press q/Esc again to quit | Ctrl+C to force quit"
                         .to_string();
                 }
-                KeyCode::Up => {
-                    if state.selected > 0 {
-                        state.selected -= 1;
-                    }
+                KeyCode::Up if state.selected > 0 => {
+                    state.selected -= 1;
                 }
-                KeyCode::Down => {
-                    if state.selected + 1 < state.rows.len() {
-                        state.selected += 1;
-                    }
+                KeyCode::Down if state.selected + 1 < state.rows.len() => {
+                    state.selected += 1;
                 }
                 KeyCode::Char('y') => {
                     if let (Some(cb), Some(row)) =
@@ -214,4 +295,49 @@ async fn run_tui(mut rx: Receiver<UiEvent>) {
 
     cleanup_terminal(terminal);
     guard.cleaned = true;
+
+    // Quitting while work is still running stops that work instead of leaving it orphaned.
+    if !state.finished {
+        cancel.cancel();
+    }
+}
+
+/// Handle a key while fixes are being reviewed; returns a new footer message, if any.
+fn review_key(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    review: &mut ReviewState,
+    key: KeyEvent,
+) -> Option<String> {
+    match key.code {
+        KeyCode::Up if review.selected > 0 => review.selected -= 1,
+        KeyCode::Down if review.selected + 1 < review.hunks.len() => review.selected += 1,
+        KeyCode::Char('a' | 'y') => review.decide(Decision::Accepted),
+        KeyCode::Char('r' | 'n') => review.decide(Decision::Rejected),
+        KeyCode::Char('A') => review.decide_pending(Decision::Accepted),
+        KeyCode::Char('e') => {
+            let (path, hunk) = review.selected_mut()?;
+            let path = path.to_string();
+            return match edit_in_terminal(terminal, &path, hunk) {
+                Ok(()) => Some(REVIEW_FOOTER.to_string()),
+                Err(err) => Some(format!("edit failed: {err:#}")),
+            };
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Hand the terminal over to the editor for a hunk, then take it back.
+fn edit_in_terminal(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    path: &str,
+    hunk: &mut Hunk,
+) -> anyhow::Result<()> {
+    let _ = disable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen, cursor::Show);
+    let result = review::edit_hunk(path, hunk);
+    let _ = enable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), EnterAlternateScreen, cursor::Hide);
+    let _ = terminal.clear();
+    result
 }
@@ -9,7 +9,10 @@ use crossterm::style::{Color, ResetColor, SetForegroundColor};
 use crossterm::{cursor, execute, terminal};
 use tokio::sync::mpsc::Receiver;
 
+use crate::review::{self, Decision, ProposedFix, hunk_refs};
+use crate::transcript::AgentStep;
 use crate::ui::events::{StreamType, UiEvent};
+use crate::ui::render::format_duration;
 
 /// Braille spinner frames.
 const SPINNER: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
@@ -94,6 +97,8 @@ pub async fn run_cli(mut rx: Receiver<UiEvent>, use_color: bool, verbose: bool)
     let mut running: HashSet<String> = HashSet::new();
     let mut spinner_tick: usize = 0;
     let mut cursor_hidden = false;
+    // Printed once at the end: the totals keep growing with each fix round.
+    let mut solver_costs = None;
 
     loop {
         // Process all available events
@@ -103,18 +108,40 @@ pub async fn run_cli(mut rx: Receiver<UiEvent>, use_color: bool, verbose: bool)
                     clear_spinner_line();
 
                     match ev {
-                        UiEvent::CheckStarted { name, desc } => {
+                        UiEvent::CheckStarted {
+                            name,
+                            desc,
+                            expected,
+                        } => {
                             running.insert(name.clone());
-                            print_started(&name, desc.as_deref(), style);
+                            print_started(&name, desc.as_deref(), expected, style);
                         }
                         UiEvent::CheckFinished {
                             name,
                             success,
                             message,
+                            flaky,
+                            advisory,
+                            log_path,
                             ..
                         } => {
                             running.remove(&name);
-                            print_finished(&name, success, &message, style);
+                            print_finished(&name, success, flaky, advisory, &message, style);
+                            if let Some(path) = log_path.filter(|_| !success) {
+                                cprint(
+                                    style,
+                                    Color::DarkGrey,
+                                    &format!("  full log: {}", path.display()),
+                                );
+                                eprintln!();
+                            }
+                        }
+                        UiEvent::CheckFlagged { name, reason } => {
+                            print_flagged(&name, &reason, style);
+                        }
+                        UiEvent::CheckCancelled { name } => {
+                            running.remove(&name);
+                            print_cancelled(&name, style);
                         }
                         UiEvent::StreamLine {
                             source,
@@ -126,9 +153,59 @@ pub async fn run_cli(mut rx: Receiver<UiEvent>, use_color: bool, verbose: bool)
                                 print_stream(&source, stream, &line, style);
                             }
                         }
+                        UiEvent::AgentStep { source, step } => {
+                            if verbose {
+                                print_step(&source, &step, style);
+                            }
+                        }
+                        UiEvent::RunEstimate { expected } => {
+                            cprint(
+                                style,
+                                Color::DarkGrey,
+                                &format!("estimated run time ~{}", format_duration(expected)),
+                            );
+                            eprintln!();
+                        }
+                        UiEvent::RunSummary {
+                            passed,
+                            failed,
+                            warnings,
+                            cancelled,
+                            stopped_by,
+                        } => {
+                            print_summary(
+                                passed,
+                                failed,
+                                warnings,
+                                cancelled,
+                                stopped_by.as_deref(),
+                                style,
+                            );
+                        }
+                        UiEvent::FixRound {
+                            round,
+                            max_rounds,
+                            failing,
+                        } => {
+                            print_fix_round(round, max_rounds, failing, style);
+                        }
+                        UiEvent::Review { mut fixes, reply } => {
+                            if cursor_hidden {
+                                let _ = execute!(stderr(), cursor::Show);
+                                cursor_hidden = false;
+                            }
+                            // Reading answers blocks; keep it off the runtime's async workers.
+                            tokio::task::block_in_place(|| review_hunks(&mut fixes, style));
+                            reply.send(fixes);
+                        }
+                        UiEvent::SolverCosts(totals) => solver_costs = Some(totals),
                         UiEvent::PoolStats(_) => {}
-                        UiEvent::Done => {
+                        UiEvent::Done | UiEvent::Exit => {
                             clear_spinner_line();
+                            if let Some(totals) = solver_costs.take() {
+                                cprint(style, Color::DarkGrey, &format!("solvers: {totals}"));
+                                eprintln!();
+                            }
                             if cursor_hidden {
                                 let _ = execute!(stderr(), cursor::Show);
                             }
@@ -165,25 +242,161 @@ pub async fn run_cli(mut rx: Receiver<UiEvent>, use_color: bool, verbose: bool)
     }
 }
 
-fn print_started(name: &str, desc: Option<&str>, style: Style) {
+fn print_started(name: &str, desc: Option<&str>, expected: Option<Duration>, style: Style) {
     cprint(style, Color::Cyan, &format!("● {name}"));
     if let Some(desc) = desc {
         cprint(style, Color::DarkGrey, &format!(" {desc}"));
     }
+    if let Some(expected) = expected {
+        cprint(
+            style,
+            Color::DarkGrey,
+            &format!(" (usually {})", format_duration(expected)),
+        );
+    }
     eprintln!();
 }
 
-fn print_finished(name: &str, success: bool, message: &str, style: Style) {
-    let (symbol, color) = if success {
-        ("✓", Color::Green)
-    } else {
-        ("✗", Color::Red)
+fn print_finished(
+    name: &str,
+    success: bool,
+    flaky: bool,
+    advisory: bool,
+    message: &str,
+    style: Style,
+) {
+    let (symbol, color) = match (success, flaky) {
+        (true, true) => ("~", Color::Yellow),
+        (true, false) => ("✓", Color::Green),
+        (false, _) if advisory => ("✗", Color::Yellow),
+        (false, _) => ("✗", Color::Red),
     };
     cprint(style, color, &format!("{symbol} {name}"));
     cprint(style, Color::DarkGrey, &format!(": {message}"));
     eprintln!();
 }
 
+fn print_summary(
+    passed: usize,
+    failed: usize,
+    warnings: usize,
+    cancelled: usize,
+    stopped_by: Option<&str>,
+    style: Style,
+) {
+    let color = if failed > 0 {
+        Color::Red
+    } else if warnings > 0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    let warnings = if warnings > 0 {
+        format!(", {warnings} warnings")
+    } else {
+        String::new()
+    };
+    cprint(
+        style,
+        color,
+        &format!("{passed} passed, {failed} failed{warnings}, {cancelled} cancelled"),
+    );
+    if let Some(name) = stopped_by {
+        cprint(
+            style,
+            Color::DarkGrey,
+            &format!(" (stopped early: '{name}' failed)"),
+        );
+    }
+    eprintln!();
+}
+
+fn print_fix_round(round: usize, max_rounds: usize, failing: usize, style: Style) {
+    cprint(
+        style,
+        Color::Cyan,
+        &format!("fix round {round}/{max_rounds}: {failing} check(s) failing"),
+    );
+    eprintln!();
+}
+
+/// Ask about each proposed hunk in turn, like `git add -p`. Hunks left unanswered (`q`, or
+/// end of input) are not applied.
+fn review_hunks(fixes: &mut [ProposedFix], style: Style) {
+    let refs = hunk_refs(fixes);
+    let total = refs.len();
+    let mut accept_rest = false;
+    for (idx, at) in refs.into_iter().enumerate() {
+        let fix = &mut fixes[at.fix];
+        let file = &mut fix.files[at.file];
+        if accept_rest {
+            file.hunks[at.hunk].decision = Decision::Accepted;
+            continue;
+        }
+        eprintln!();
+        cprint(
+            style,
+            Color::Cyan,
+            &format!("solve:{} {} ({}/{total})", fix.check, file.path, idx + 1),
+        );
+        eprintln!();
+        for line in file.display_lines(&file.hunks[at.hunk]) {
+            print_diff_line(line, style);
+        }
+        loop {
+            eprint!("Apply this hunk? [y]es [n]o [e]dit [a]ll remaining [q]uit: ");
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+                return;
+            }
+            let hunk = &mut file.hunks[at.hunk];
+            match answer.trim() {
+                "y" => hunk.decision = Decision::Accepted,
+                "n" => hunk.decision = Decision::Rejected,
+                "a" => {
+                    hunk.decision = Decision::Accepted;
+                    accept_rest = true;
+                }
+                "e" => {
+                    if let Err(err) = review::edit_hunk(&file.path, hunk) {
+                        cprint(style, Color::Red, &format!("{err:#}"));
+                        eprintln!();
+                        continue;
+                    }
+                }
+                "q" => return,
+                _ => continue,
+            }
+            break;
+        }
+    }
+}
+
+fn print_diff_line(line: &str, style: Style) {
+    let color = if line.starts_with("@@") {
+        Color::Cyan
+    } else if line.starts_with('+') {
+        Color::Green
+    } else if line.starts_with('-') {
+        Color::Red
+    } else {
+        Color::Reset
+    };
+    cprint(style, color, line);
+    eprintln!();
+}
+
+fn print_flagged(name: &str, reason: &str, style: Style) {
+    cprint(style, Color::Yellow, &format!("! {name}"));
+    cprint(style, Color::DarkGrey, &format!(": {reason}"));
+    eprintln!();
+}
+
+fn print_cancelled(name: &str, style: Style) {
+    cprint(style, Color::DarkGrey, &format!("- {name}: cancelled"));
+    eprintln!();
+}
+
 fn print_stream(source: &str, stream: StreamType, line: &str, style: Style) {
     let color = match stream {
         StreamType::Stdout => Color::DarkGrey,
@@ -194,6 +407,19 @@ fn print_stream(source: &str, stream: StreamType, line: &str, style: Style) {
     eprintln!("{line}");
 }
 
+fn print_step(source: &str, step: &AgentStep, style: Style) {
+    let color = match step {
+        AgentStep::Message(_) | AgentStep::Summary(_) => Color::Reset,
+        AgentStep::ToolCall { .. } => Color::Cyan,
+        AgentStep::FileEdit { .. } => Color::Green,
+        AgentStep::Usage(_) => Color::DarkGrey,
+        AgentStep::Error(_) => Color::Red,
+    };
+    cprint(style, Color::DarkGrey, &format!("│ [{source}] "));
+    cprint(style, color, &step.to_string());
+    eprintln!();
+}
+
 fn print_spinner(running: &HashSet<String>, tick: usize) {
     let frame = SPINNER[tick % SPINNER.len()];
     let names: Vec<&str> = running.iter().map(|s| s.as_str()).collect();
@@ -1,4 +1,10 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cost::CostTotals;
 use crate::pool::PoolStats;
+use crate::review::{ProposedFix, ReviewReply};
+use crate::transcript::AgentStep;
 
 /// Type of output stream.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -10,14 +16,57 @@ pub enum StreamType {
 #[derive(Debug, Clone)]
 pub enum UiEvent {
     /// A check/task has started running.
-    CheckStarted { name: String, desc: Option<String> },
+    CheckStarted {
+        name: String,
+        desc: Option<String>,
+        /// How long it usually takes, when known.
+        expected: Option<Duration>,
+    },
     /// A check/task has finished.
     CheckFinished {
         name: String,
         success: bool,
         message: String,
         output: Option<String>,
+        /// Passed, but only after a retry.
+        flaky: bool,
+        /// Failures of this check are warnings only.
+        advisory: bool,
+        /// File holding the full output, if it was logged.
+        log_path: Option<PathBuf>,
+    },
+    /// Draw attention to a check (e.g. repeatedly flaky) with a short reason.
+    CheckFlagged { name: String, reason: String },
+    /// A check/task was stopped before it finished because the run was cancelled.
+    CheckCancelled { name: String },
+    /// Estimated wall time of the whole run, sent when it starts.
+    RunEstimate { expected: Duration },
+    /// Totals for a finished check phase.
+    RunSummary {
+        passed: usize,
+        failed: usize,
+        /// Failed advisory checks.
+        warnings: usize,
+        cancelled: usize,
+        /// The failing check that stopped the run early (`--fail-fast` or `critical`).
+        stopped_by: Option<String>,
+    },
+    /// A round of fixes is starting.
+    FixRound {
+        round: usize,
+        max_rounds: usize,
+        /// Checks still failing the run going into this round (advisory and cancelled ones
+        /// aside).
+        failing: usize,
+    },
+    /// Solvers proposed fixes (`--review`); the UI lets the developer decide on each hunk and
+    /// sends them back through `reply`.
+    Review {
+        fixes: Vec<ProposedFix>,
+        reply: ReviewReply,
     },
+    /// What solvers have spent so far in this run, sent after each fix round.
+    SolverCosts(CostTotals),
     /// Pool statistics update.
     PoolStats(PoolStats),
     /// A line of output from a running process.
@@ -26,6 +75,10 @@ pub enum UiEvent {
         stream: StreamType,
         line: String,
     },
+    /// A step taken by an agent whose output is structured (messages, tool calls, edits).
+    AgentStep { source: String, step: AgentStep },
     /// All work is done.
     Done,
+    /// The run was abandoned (a second Ctrl+C): close the UI without waiting for the user.
+    Exit,
 }
@@ -7,4 +7,5 @@ mod state;
 
 pub use app::spawn_ui;
 pub use events::{StreamType, UiEvent};
+pub(crate) use render::format_duration;
 pub(crate) use sanitize::sanitize_text_for_tui;
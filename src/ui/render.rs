@@ -1,4 +1,5 @@
 use std::io::Stdout;
+use std::time::{Duration, Instant};
 
 use crossterm::cursor;
 use crossterm::execute;
@@ -8,8 +9,10 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
 
 use crate::pool::PoolStats;
+use crate::review::Decision;
 use crate::ui::events::StreamType;
-use crate::ui::state::{AppState, CheckRow};
+use crate::ui::sanitize_text_for_tui;
+use crate::ui::state::{AppState, CheckRow, ReviewState};
 
 /// Braille spinner frames for running tasks.
 const BRAILLE_SPINNER: &[&str] = &[
@@ -23,6 +26,20 @@ const BRAILLE_SPINNER: &[&str] = &[
     "\u{28fe}", // ⣾
 ];
 
+/// Format a duration compactly for display, e.g. `<1s`, `45s`, `3m05s` or `1h02m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        "<1s".to_string()
+    } else if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
 /// Get spinner frame for given tick.
 pub fn spinner_frame(tick: usize) -> &'static str {
     BRAILLE_SPINNER[tick % BRAILLE_SPINNER.len()]
@@ -33,6 +50,11 @@ pub(crate) fn draw(
     state: &AppState,
     footer_msg: &str,
 ) {
+    if let Some(review) = &state.review {
+        draw_review(terminal, review, footer_msg);
+        return;
+    }
+
     let items: Vec<ListItem> = state
         .rows
         .iter()
@@ -40,7 +62,17 @@ pub(crate) fn draw(
         .map(|(idx, row)| list_item(row, idx == state.selected, state.spinner_tick))
         .collect();
 
-    let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Checks"));
+    let mut title = match &state.summary {
+        Some(summary) => format!("Checks: {summary}"),
+        None => "Checks".to_string(),
+    };
+    if let Some((round, max_rounds)) = state.fix_round {
+        title.push_str(&format!(" | fix round {round}/{max_rounds}"));
+    }
+    if let Some(costs) = &state.solver_costs {
+        title.push_str(&format!(" | {costs}"));
+    }
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
 
     // Build detail panel content
     let detail_content = build_detail_content(state);
@@ -69,7 +101,12 @@ pub(crate) fn draw(
         frame.render_widget(detail, columns[1]);
 
         // Render pool bar
-        let pool_bar = render_pool_bar(state.pool_stats.as_ref(), outer[1].width as usize);
+        let run_deadline = state.run_deadline.filter(|_| !state.finished);
+        let pool_bar = render_pool_bar(
+            state.pool_stats.as_ref(),
+            run_deadline,
+            outer[1].width as usize,
+        );
         let pool_widget = Paragraph::new(pool_bar);
         frame.render_widget(pool_widget, outer[1]);
 
@@ -81,12 +118,92 @@ pub(crate) fn draw(
     });
 }
 
+/// The review screen: proposed hunks on the left, the selected one's diff on the right.
+fn draw_review(
+    terminal: &mut ratatui::Terminal<CrosstermBackend<Stdout>>,
+    review: &ReviewState,
+    footer_msg: &str,
+) {
+    let items: Vec<ListItem> = review
+        .hunks
+        .iter()
+        .enumerate()
+        .map(|(idx, at)| {
+            let (check, file, hunk) = review.hunk(*at);
+            let (mark, color) = match hunk.decision {
+                Decision::Pending => ("[ ]", Color::White),
+                Decision::Accepted => ("[+]", Color::Green),
+                Decision::Rejected => ("[-]", Color::DarkGray),
+            };
+            let mut style = Style::default().fg(color);
+            if idx == review.selected {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            let indicator = if idx == review.selected { "|" } else { " " };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{indicator} {mark} "), style),
+                Span::styled(format!("{check}: {}", file.path), style),
+            ]))
+        })
+        .collect();
+    let (accepted, rejected) = review.counts();
+    let title = format!(
+        "Review: {} hunks, {accepted} accepted, {rejected} rejected",
+        review.hunks.len()
+    );
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+
+    let diff: Vec<Line> = match review.hunks.get(review.selected) {
+        Some(at) => {
+            let (_, file, hunk) = review.hunk(*at);
+            file.display_lines(hunk)
+                .iter()
+                .map(|line| {
+                    let color = if line.starts_with("@@") {
+                        Color::Cyan
+                    } else if line.starts_with('+') {
+                        Color::Green
+                    } else if line.starts_with('-') {
+                        Color::Red
+                    } else {
+                        Color::Gray
+                    };
+                    Line::styled(sanitize_text_for_tui(line), Style::default().fg(color))
+                })
+                .collect()
+        }
+        None => vec![Line::raw("(no changes)")],
+    };
+    let detail = Paragraph::new(diff).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Proposed change"),
+    );
+
+    let _ = terminal.draw(|frame| {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.area());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(outer[0]);
+        frame.render_widget(list, columns[0]);
+        frame.render_widget(detail, columns[1]);
+        let footer = Paragraph::new(footer_msg.to_string())
+            .style(Style::default().fg(Color::Gray))
+            .wrap(Wrap { trim: true });
+        frame.render_widget(footer, outer[1]);
+    });
+}
+
 /// Build the detail panel content - shows selected check details or live stream.
 fn build_detail_content(state: &AppState) -> String {
     // If we have a selected row, show its details
     if let Some(row) = state.rows.get(state.selected) {
         // If the selected row is running, show live stream for it
-        if row.success.is_none() && !state.stream_buffer.is_empty() {
+        if row.success.is_none() && !row.cancelled && !state.stream_buffer.is_empty() {
             let mut lines = Vec::new();
             lines.push(format!("Check: {}", row.name));
             lines.push("Status: running".to_string());
@@ -140,7 +257,11 @@ fn build_detail_content(state: &AppState) -> String {
 }
 
 /// Render the pool utilization bar.
-fn render_pool_bar(stats: Option<&PoolStats>, width: usize) -> Line<'static> {
+fn render_pool_bar(
+    stats: Option<&PoolStats>,
+    run_deadline: Option<Instant>,
+    width: usize,
+) -> Line<'static> {
     let Some(stats) = stats else {
         return Line::from(vec![Span::styled(
             " Pool: --",
@@ -149,12 +270,13 @@ fn render_pool_bar(stats: Option<&PoolStats>, width: usize) -> Line<'static> {
     };
 
     // Calculate bar width (leave room for text)
-    let text_width = 25; // " [........] N/N active (Q queued)"
+    let text_width = 55; // " [........] N/N slots, N running (+Q queued), mem N/NM, ETA ~Ns"
+    let text_width = text_width + if stats.agents.is_some() { 25 } else { 0 }; // " | agents N/N (+Q queued)"
     let bar_width = width.saturating_sub(text_width).max(8);
 
-    // Calculate filled portion
+    // Calculate filled portion (weighted checks hold several slots)
     let filled = if stats.capacity > 0 {
-        (stats.active as f64 / stats.capacity as f64 * bar_width as f64).round() as usize
+        (stats.used as f64 / stats.capacity as f64 * bar_width as f64).round() as usize
     } else {
         0
     };
@@ -165,9 +287,9 @@ fn render_pool_bar(stats: Option<&PoolStats>, width: usize) -> Line<'static> {
     let empty_str: String = "\u{2591}".repeat(empty); // ░
 
     // Color based on utilization
-    let bar_color = if stats.active == stats.capacity {
+    let bar_color = if stats.used >= stats.capacity {
         Color::Red
-    } else if stats.active > stats.capacity / 2 {
+    } else if stats.used > stats.capacity / 2 {
         Color::Yellow
     } else {
         Color::Green
@@ -181,14 +303,34 @@ fn render_pool_bar(stats: Option<&PoolStats>, width: usize) -> Line<'static> {
     ];
 
     // Add stats text
-    let stats_text = if stats.queued > 0 {
+    let mut stats_text = if stats.used == stats.active {
+        format!("{}/{} active", stats.active, stats.capacity)
+    } else {
         format!(
-            "{}/{} active (+{} queued)",
-            stats.active, stats.capacity, stats.queued
+            "{}/{} slots, {} running",
+            stats.used, stats.capacity, stats.active
         )
-    } else {
-        format!("{}/{} active", stats.active, stats.capacity)
     };
+    if stats.queued > 0 {
+        stats_text.push_str(&format!(" (+{} queued)", stats.queued));
+    }
+    if let Some(budget) = stats.memory_budget_mb {
+        stats_text.push_str(&format!(", mem {}/{}M", stats.memory_used_mb, budget));
+    }
+    if let Some(agents) = stats.agents {
+        stats_text.push_str(&format!(" | agents {}/{}", agents.active, agents.capacity));
+        if agents.queued > 0 {
+            stats_text.push_str(&format!(" (+{} queued)", agents.queued));
+        }
+    }
+    if let Some(deadline) = run_deadline {
+        let now = Instant::now();
+        if deadline > now {
+            stats_text.push_str(&format!(", ETA ~{}", format_duration(deadline - now)));
+        } else {
+            stats_text.push_str(", ETA overdue");
+        }
+    }
 
     spans.push(Span::styled(stats_text, Style::default().fg(Color::White)));
 
@@ -198,23 +340,48 @@ fn render_pool_bar(stats: Option<&PoolStats>, width: usize) -> Line<'static> {
 pub(crate) fn detail_text(row: &CheckRow) -> String {
     let desc = &row.desc;
     let status = match row.success {
+        _ if row.cancelled => "cancelled",
+        Some(true) if row.flaky => "passed (flaky)",
         Some(true) => "passed",
+        Some(false) if row.advisory => "failed (advisory)",
         Some(false) => "failed",
         None => "running",
     };
     let output = row.output.as_deref().unwrap_or("").trim();
-    format!(
-        "Check: {}\nStatus: {}\nMessage: {}\nDescription: {}\n\nOutput:\n{}",
+    let mut text = format!(
+        "Check: {}\nStatus: {}\nMessage: {}\nDescription: {}\n",
         row.name,
         status,
         row.status,
         desc.as_deref().unwrap_or(""),
+    );
+    for flag in &row.flags {
+        text.push_str(&format!("Flag: {flag}\n"));
+    }
+    if let Some(path) = &row.log_path {
+        text.push_str(&format!("Full log: {}\n", path.display()));
+    }
+    // Only reruns (watch mode) have a history worth showing.
+    if row.history.len() > 1 {
+        text.push_str("\nHistory (newest first):\n");
+        for entry in row.history.iter().rev() {
+            let symbol = if entry.success { "ok" } else { "X" };
+            text.push_str(&format!(
+                "  [{symbol}] {} ({}s ago)\n",
+                entry.message,
+                entry.finished_at.elapsed().as_secs()
+            ));
+        }
+    }
+    text.push_str(&format!(
+        "\nOutput:\n{}",
         if output.is_empty() {
             "(no output)"
         } else {
             output
         }
-    )
+    ));
+    text
 }
 
 pub(crate) fn cleanup_terminal(mut terminal: ratatui::Terminal<CrosstermBackend<Stdout>>) {
@@ -226,12 +393,18 @@ pub(crate) fn cleanup_terminal(mut terminal: ratatui::Terminal<CrosstermBackend<
 
 fn list_item(row: &CheckRow, is_selected: bool, spinner_tick: usize) -> ListItem<'static> {
     let status = match row.success {
+        _ if row.cancelled => "[-]".to_string(),
+        Some(true) if row.flaky => "[~]".to_string(),
         Some(true) => "[OK]".to_string(),
+        Some(false) if row.advisory => "[!]".to_string(),
         Some(false) => "[X]".to_string(),
         None => format!(" {} ", spinner_frame(spinner_tick)),
     };
     let base_style = match row.success {
+        _ if row.cancelled => Style::default().fg(Color::DarkGray),
+        Some(true) if row.flaky => Style::default().fg(Color::Yellow),
         Some(true) => Style::default().fg(Color::Green),
+        Some(false) if row.advisory => Style::default().fg(Color::Yellow),
         Some(false) => Style::default().fg(Color::Red),
         None => Style::default().fg(Color::Cyan),
     };
@@ -242,12 +415,34 @@ fn list_item(row: &CheckRow, is_selected: bool, spinner_tick: usize) -> ListItem
     };
     let status_style = line_style.add_modifier(Modifier::BOLD);
     let indicator = if is_selected { "|" } else { " " };
-    let line = Line::from(vec![
+    let mut spans = vec![
         Span::styled(indicator.to_string(), line_style),
         Span::raw(" "),
         Span::styled(status, status_style),
         Span::raw(" "),
         Span::styled(row.name.clone(), line_style),
-    ]);
+    ];
+    if row.success.is_none() && !row.cancelled {
+        match row.eta() {
+            Some(Ok(remaining)) => spans.push(Span::styled(
+                format!(" ~{} left", format_duration(remaining)),
+                Style::default().fg(Color::DarkGray),
+            )),
+            Some(Err(overrun)) => spans.push(Span::styled(
+                format!(" +{} over", format_duration(overrun)),
+                Style::default().fg(Color::Yellow),
+            )),
+            None => {}
+        }
+    }
+    if !row.flags.is_empty() {
+        spans.push(Span::styled(
+            " !",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    let line = Line::from(spans);
     ListItem::new(line)
 }
@@ -1,12 +1,27 @@
 use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use crate::cost::CostTotals;
 use crate::pool::PoolStats;
+use crate::review::{Decision, FilePatch, Hunk, HunkRef, ProposedFix, ReviewReply, hunk_refs};
 use crate::ui::events::StreamType;
 use crate::ui::sanitize_text_for_tui;
 
 /// Maximum number of stream lines to keep in buffer.
 const MAX_STREAM_LINES: usize = 200;
 
+/// Maximum number of past results kept per row (used by watch mode reruns).
+const MAX_HISTORY: usize = 10;
+
+/// Outcome of a previous run of a row's check.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub success: bool,
+    pub message: String,
+    pub finished_at: Instant,
+}
+
 #[derive(Debug, Clone)]
 pub struct CheckRow {
     pub name: String,
@@ -14,6 +29,22 @@ pub struct CheckRow {
     pub success: Option<bool>,
     pub desc: Option<String>,
     pub output: Option<String>,
+    /// File holding the full output of the last run, if it was logged.
+    pub log_path: Option<PathBuf>,
+    /// Set when the run was cancelled before the check finished.
+    pub cancelled: bool,
+    /// The last run passed only after a retry.
+    pub flaky: bool,
+    /// Failures are warnings only (advisory check).
+    pub advisory: bool,
+    /// Reasons this check needs attention (e.g. repeatedly flaky).
+    pub flags: Vec<String>,
+    /// Rolling history of finished runs, newest last.
+    pub history: VecDeque<HistoryEntry>,
+    /// When the current run started.
+    pub started_at: Instant,
+    /// How long the check usually takes, when known.
+    pub expected: Option<Duration>,
 }
 
 impl CheckRow {
@@ -24,8 +55,71 @@ impl CheckRow {
             success: None,
             desc,
             output: Some("running".into()),
+            log_path: None,
+            cancelled: false,
+            flaky: false,
+            advisory: false,
+            flags: Vec::new(),
+            history: VecDeque::new(),
+            started_at: Instant::now(),
+            expected: None,
+        }
+    }
+
+    /// Reset the row for a new run.
+    pub fn restart(&mut self, desc: Option<String>) {
+        self.started_at = Instant::now();
+        self.status = "running".into();
+        self.success = None;
+        self.cancelled = false;
+        self.flaky = false;
+        self.output = Some("running".into());
+        self.log_path = None;
+        self.desc = desc;
+    }
+
+    /// Expected time left for a running check: `Ok(remaining)`, or `Err(overrun)` once it
+    /// has taken longer than usual. `None` when there is no estimate.
+    pub fn eta(&self) -> Option<Result<Duration, Duration>> {
+        let expected = self.expected?;
+        let elapsed = self.started_at.elapsed();
+        Some(if elapsed <= expected {
+            Ok(expected - elapsed)
+        } else {
+            Err(elapsed - expected)
+        })
+    }
+
+    /// Attach a reason for attention, ignoring duplicates.
+    pub fn flag(&mut self, reason: String) {
+        if !self.flags.contains(&reason) {
+            self.flags.push(reason);
         }
     }
+
+    /// Mark the current run as cancelled.
+    pub fn cancel(&mut self) {
+        self.status = "cancelled".into();
+        self.success = None;
+        self.cancelled = true;
+    }
+
+    /// Record a finished run, updating the current status and the rolling history.
+    pub fn finish(&mut self, success: bool, message: String, output: Option<String>, flaky: bool) {
+        if self.history.len() >= MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(HistoryEntry {
+            success,
+            message: message.clone(),
+            finished_at: Instant::now(),
+        });
+        self.success = Some(success);
+        self.cancelled = false;
+        self.flaky = flaky;
+        self.status = message;
+        self.output = output;
+    }
 }
 
 /// A single line of streamed output.
@@ -36,12 +130,91 @@ pub struct StreamLine {
     pub line: String,
 }
 
+/// Proposed fixes being reviewed hunk by hunk (`--review`).
+#[derive(Debug)]
+pub struct ReviewState {
+    pub fixes: Vec<ProposedFix>,
+    pub hunks: Vec<HunkRef>,
+    pub selected: usize,
+    reply: ReviewReply,
+}
+
+impl ReviewState {
+    pub fn new(fixes: Vec<ProposedFix>, reply: ReviewReply) -> Self {
+        Self {
+            hunks: hunk_refs(&fixes),
+            fixes,
+            selected: 0,
+            reply,
+        }
+    }
+
+    /// The check, file and hunk at `at`.
+    pub fn hunk(&self, at: HunkRef) -> (&str, &FilePatch, &Hunk) {
+        let fix = &self.fixes[at.fix];
+        let file = &fix.files[at.file];
+        (&fix.check, file, &file.hunks[at.hunk])
+    }
+
+    /// The selected hunk and the path of its file.
+    pub fn selected_mut(&mut self) -> Option<(&str, &mut Hunk)> {
+        let at = *self.hunks.get(self.selected)?;
+        let file = &mut self.fixes[at.fix].files[at.file];
+        Some((&file.path, &mut file.hunks[at.hunk]))
+    }
+
+    /// Decide on the selected hunk and move on to the next one.
+    pub fn decide(&mut self, decision: Decision) {
+        if let Some((_, hunk)) = self.selected_mut() {
+            hunk.decision = decision;
+        }
+        if self.selected + 1 < self.hunks.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Decide on every hunk not decided yet.
+    pub fn decide_pending(&mut self, decision: Decision) {
+        let hunks = self.fixes.iter_mut().flat_map(|fix| &mut fix.files);
+        for hunk in hunks.flat_map(|file| &mut file.hunks) {
+            if hunk.decision == Decision::Pending {
+                hunk.decision = decision;
+            }
+        }
+    }
+
+    /// Number of accepted and rejected hunks.
+    pub fn counts(&self) -> (usize, usize) {
+        let decisions = self.hunks.iter().map(|at| self.hunk(*at).2.decision);
+        decisions.fold((0, 0), |(accepted, rejected), decision| match decision {
+            Decision::Accepted => (accepted + 1, rejected),
+            Decision::Rejected => (accepted, rejected + 1),
+            Decision::Pending => (accepted, rejected),
+        })
+    }
+
+    /// Hand the decisions back; hunks still pending are not applied.
+    pub fn finish(self) {
+        self.reply.send(self.fixes);
+    }
+}
+
 /// Application state for the TUI.
 #[derive(Debug)]
 pub struct AppState {
     pub rows: Vec<CheckRow>,
     pub selected: usize,
     pub pool_stats: Option<PoolStats>,
+    /// When the whole run is expected to finish, if it could be estimated.
+    pub run_deadline: Option<Instant>,
+    /// Totals of the last finished check phase.
+    pub summary: Option<String>,
+    /// Current fix round and the maximum, once fixing has started.
+    pub fix_round: Option<(usize, usize)>,
+    /// What solvers have spent so far.
+    pub solver_costs: Option<CostTotals>,
+    /// Fixes waiting for review; while set, the TUI shows them instead of the checks.
+    pub review: Option<ReviewState>,
     pub stream_buffer: VecDeque<StreamLine>,
     pub finished: bool,
     pub exit_requested: bool,
@@ -55,6 +228,11 @@ impl AppState {
             rows: Vec::new(),
             selected: 0,
             pool_stats: None,
+            run_deadline: None,
+            summary: None,
+            fix_round: None,
+            solver_costs: None,
+            review: None,
             stream_buffer: VecDeque::with_capacity(MAX_STREAM_LINES),
             finished: false,
             exit_requested: false,
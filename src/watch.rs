@@ -0,0 +1,352 @@
+//! Watch mode: rerun affected checks when files under the root change.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::config::{Check, Config, OutputConfig};
+use crate::history;
+use crate::logs::RunLogs;
+use crate::pool::Pool;
+use crate::redact::Redactor;
+use crate::runner::{self, CheckContext, CheckResult};
+use crate::ui::UiEvent;
+
+/// A check together with the compiled globs that trigger it.
+pub(crate) struct WatchTarget {
+    check: Check,
+    /// `None` means any relevant change triggers the check.
+    matcher: Option<GlobSet>,
+}
+
+impl WatchTarget {
+    pub(crate) fn new(check: Check) -> Result<Self> {
+        if check.watch.is_empty() {
+            return Ok(Self {
+                check,
+                matcher: None,
+            });
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &check.watch {
+            let glob = Glob::new(pattern).with_context(|| {
+                format!("invalid watch glob '{pattern}' for check '{}'", check.name)
+            })?;
+            builder.add(glob);
+        }
+        let matcher = builder
+            .build()
+            .with_context(|| format!("invalid watch globs for check '{}'", check.name))?;
+        Ok(Self {
+            check,
+            matcher: Some(matcher),
+        })
+    }
+
+    pub(crate) fn is_affected_by(&self, changed: &BTreeSet<PathBuf>) -> bool {
+        match &self.matcher {
+            Some(matcher) => changed.iter().any(|path| matcher.is_match(path)),
+            None => !changed.is_empty(),
+        }
+    }
+}
+
+/// Filters out changes that should never trigger a rerun (git internals, ignored files).
+struct ChangeFilter {
+    root: PathBuf,
+    gitignore: Gitignore,
+}
+
+impl ChangeFilter {
+    fn new(root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        // Missing files are fine; only parse errors are reported, and those we tolerate.
+        let _ = builder.add(root.join(".gitignore"));
+        let _ = builder.add(root.join(".git").join("info").join("exclude"));
+        let gitignore = builder.build().unwrap_or_else(|_| Gitignore::empty());
+        Self {
+            root: root.to_path_buf(),
+            gitignore,
+        }
+    }
+
+    /// Returns the root-relative path if the change is relevant.
+    fn relevant(&self, path: &Path) -> Option<PathBuf> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        if rel.as_os_str().is_empty()
+            || rel.starts_with(".git")
+            || rel.starts_with(history::STATE_DIR)
+        {
+            return None;
+        }
+        let is_dir = path.is_dir();
+        if self
+            .gitignore
+            .matched_path_or_any_parents(rel, is_dir)
+            .is_ignore()
+        {
+            return None;
+        }
+        Some(rel.to_path_buf())
+    }
+}
+
+/// A prepared watch session: compiled targets plus an active file watcher.
+pub struct WatchSession {
+    root: PathBuf,
+    targets: Vec<WatchTarget>,
+    lock_capacities: HashMap<String, usize>,
+    output: OutputConfig,
+    redactor: Arc<Redactor>,
+    filter: ChangeFilter,
+    // Kept alive for the duration of the session; dropping it stops notifications.
+    _watcher: notify::RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+}
+
+impl WatchSession {
+    /// Validate the watch configuration and start watching `root` for changes.
+    pub fn new(checks: Vec<Check>, config: &Config, root: &Path) -> Result<Self> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("failed to resolve root {}", root.display()))?;
+        let targets = checks
+            .into_iter()
+            .map(WatchTarget::new)
+            .collect::<Result<Vec<_>>>()?;
+        let filter = ChangeFilter::new(&root);
+
+        let (fs_tx, changes) = mpsc::unbounded_channel::<PathBuf>();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            for path in event.paths {
+                let _ = fs_tx.send(path);
+            }
+        })
+        .context("failed to create file watcher")?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch {}", root.display()))?;
+
+        Ok(Self {
+            root,
+            targets,
+            lock_capacities: config.locks.clone(),
+            output: config.output.clone(),
+            redactor: Arc::new(Redactor::new(&config.redaction)),
+            filter,
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Run every check once, then keep rerunning the ones affected by file changes
+    /// until the UI exits or the pool is cancelled. In the latter case the UI's handle is
+    /// returned so the caller can close it.
+    pub async fn run(
+        mut self,
+        pool: &Pool,
+        debounce: Duration,
+        ui_tx: Option<Sender<UiEvent>>,
+        mut ui_handle: JoinHandle<()>,
+    ) -> Option<JoinHandle<()>> {
+        let lock_groups = runner::lock_groups(
+            &self
+                .targets
+                .iter()
+                .map(|t| t.check.clone())
+                .collect::<Vec<_>>(),
+            &self.lock_capacities,
+        );
+
+        let history = history::HistoryStore::load(&self.root);
+        let ctx = CheckContext {
+            root: self.root.clone(),
+            quiet: true,
+            ui_tx: ui_tx.clone(),
+            max_output_bytes: self.output.max_bytes,
+            logs: RunLogs::create(&self.root, self.output.keep_logs),
+            redactor: self.redactor.clone(),
+        };
+
+        let mut running: HashMap<String, JoinHandle<CheckResult>> = HashMap::new();
+        let start = |check: &Check, running: &mut HashMap<String, JoinHandle<CheckResult>>| {
+            // Cancel the in-flight run (and its process) before restarting.
+            if let Some(handle) = running.remove(&check.name) {
+                handle.abort();
+            }
+            let handle = runner::spawn_check(
+                check.clone(),
+                pool,
+                lock_groups.clone(),
+                history.expected_duration(&check.name),
+                ctx.clone(),
+            );
+            running.insert(check.name.clone(), handle);
+        };
+
+        for target in &self.targets {
+            start(&target.check, &mut running);
+        }
+
+        let cancel = pool.cancel_token();
+        let mut ui_running = true;
+        // Relevant changes since the last rerun, and when to act on them: `debounce` after the
+        // first one, so a steady stream of changes can't put the rerun off forever.
+        let mut changed = BTreeSet::new();
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let quiet = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = &mut ui_handle => {
+                    ui_running = false;
+                    break;
+                }
+                _ = cancel.cancelled() => break,
+                Some(path) = self.changes.recv() => {
+                    if let Some(rel) = self.filter.relevant(&path) {
+                        changed.insert(rel);
+                        deadline.get_or_insert_with(|| Instant::now() + debounce);
+                    }
+                }
+                _ = quiet => {
+                    deadline = None;
+                    let changed = std::mem::take(&mut changed);
+                    for target in self.targets.iter().filter(|t| t.is_affected_by(&changed)) {
+                        start(&target.check, &mut running);
+                    }
+                    running.retain(|_, handle| !handle.is_finished());
+                }
+            }
+        }
+
+        for handle in running.into_values() {
+            handle.abort();
+        }
+        ui_running.then_some(ui_handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandSpec;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_check(name: &str, watch: Vec<&str>) -> Check {
+        Check {
+            name: name.to_string(),
+            command: CommandSpec {
+                program: "echo".to_string(),
+                args: vec![],
+            },
+            formatter: None,
+            fixer: None,
+            env: HashMap::new(),
+            timeout: None,
+            kill_grace: None,
+            enabled: true,
+            tags: vec![],
+            description: None,
+            cwd: None,
+            lock: None,
+            weight: 1,
+            memory_mb: None,
+            watch: watch.into_iter().map(String::from).collect(),
+            retries: 0,
+            retry_on: vec![],
+            critical: false,
+            pty: None,
+            success_exit_codes: vec![0],
+            error_exit_codes: vec![],
+            fail_if_output_matches: vec![],
+            mode: Default::default(),
+            env_policy: Default::default(),
+            fix_scope: Default::default(),
+            on_scope_violation: Default::default(),
+            prompt: Default::default(),
+        }
+    }
+
+    fn changed(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn check_without_globs_is_affected_by_any_change() {
+        let target = WatchTarget::new(make_check("all", vec![])).unwrap();
+        assert!(target.is_affected_by(&changed(&["README.md"])));
+        assert!(!target.is_affected_by(&changed(&[])));
+    }
+
+    #[test]
+    fn check_with_globs_only_matches_its_files() {
+        let target =
+            WatchTarget::new(make_check("rust", vec!["src/**/*.rs", "Cargo.toml"])).unwrap();
+        assert!(target.is_affected_by(&changed(&["src/ui/app.rs"])));
+        assert!(target.is_affected_by(&changed(&["Cargo.toml"])));
+        assert!(!target.is_affected_by(&changed(&["web/app.ts", "README.md"])));
+    }
+
+    #[test]
+    fn invalid_glob_is_reported() {
+        let err = WatchTarget::new(make_check("bad", vec!["src/[.rs"]))
+            .err()
+            .expect("expected invalid glob error");
+        assert!(format!("{err:#}").contains("bad"));
+    }
+
+    #[test]
+    fn change_filter_respects_gitignore_and_git_dir() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!(
+            "scanner-rs-watch-filter-{}-{nanos}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+
+        let filter = ChangeFilter::new(&root);
+        assert_eq!(
+            filter.relevant(&root.join("src/main.rs")),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert!(
+            filter
+                .relevant(&root.join("target/debug/scanner"))
+                .is_none()
+        );
+        assert!(filter.relevant(&root.join("build.log")).is_none());
+        assert!(filter.relevant(&root.join(".git/index")).is_none());
+        assert!(
+            filter
+                .relevant(&root.join(".scanner/history.json"))
+                .is_none()
+        );
+        assert!(filter.relevant(Path::new("/elsewhere/file.rs")).is_none());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}